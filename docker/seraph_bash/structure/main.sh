@@ -0,0 +1,78 @@
+#!/usr/bin/env bash
+# Calls `function_name` from `<module_name>.sh`, what the function prints to stdout is its return value.
+#
+# Usage: main.sh [--output-type <type>] [--with <apt package>]... module_name function_name [json_args...]
+set -euo pipefail
+
+RESULT_PATH="${SERAPH_RESULT_PATH:-/app/.seraph_result}"
+
+output_type="string"
+dependencies=()
+
+while [[ $# -gt 0 ]]; do
+  case "$1" in
+    --output-type)
+      output_type="$2"
+      shift 2
+      ;;
+    --with)
+      dependencies+=("$2")
+      shift 2
+      ;;
+    *)
+      break
+      ;;
+  esac
+done
+
+if [[ $# -lt 2 ]]; then
+  echo "should have 3 args: module_name, function_name, function_args" >&2
+  exit 1
+fi
+
+module_name="$1"
+function_name="$2"
+shift 2
+
+if [[ ${#dependencies[@]} -gt 0 ]]; then
+  # apt output goes to stderr so it does not end up in the return value
+  {
+    apt-get update -qq
+    apt-get install -y -qq --no-install-recommends "${dependencies[@]}"
+  } >&2
+fi
+
+# JSON strings are passed as plain text, other values keep their JSON encoding
+args=()
+for arg in "$@"; do
+  args+=("$(jq -r 'if type == "string" then . else tojson end' <<<"$arg")")
+done
+
+# The node code is in the working directory, not next to this harness
+# shellcheck source=/dev/null
+source "$PWD/$module_name.sh"
+
+if [[ "$(type -t "$function_name")" != "function" ]]; then
+  echo "$function_name is not a function" >&2
+  exit 1
+fi
+
+output_file="$(mktemp)"
+
+# The function runs with the shell defaults rather than the strict mode of this harness
+set +eu
+"$function_name" "${args[@]}" >"$output_file"
+status=$?
+set -eu
+
+if [[ $status -ne 0 ]]; then
+  exit "$status"
+fi
+
+# Printed text has no type, encode it as the type the node declares
+case "$output_type" in
+  string) jq -Rs 'rtrimstr("\n")' <"$output_file" >"$RESULT_PATH" ;;
+  bytes) base64 -w0 <"$output_file" | jq -Rs . >"$RESULT_PATH" ;;
+  not_output) echo null >"$RESULT_PATH" ;;
+  *) jq -c . <"$output_file" >"$RESULT_PATH" ;;
+esac
@@ -0,0 +1,3 @@
+module seraph/harness
+
+go 1.22
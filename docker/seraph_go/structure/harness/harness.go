@@ -0,0 +1,92 @@
+// Package harness calls a node function with JSON arguments and writes its JSON encoded return value.
+package harness
+
+import (
+	"encoding/json"
+	"errors"
+	"fmt"
+	"os"
+	"reflect"
+)
+
+var errorType = reflect.TypeOf((*error)(nil)).Elem()
+
+// Run calls fn with the command line arguments, each decoded from JSON into the type of its parameter.
+//
+// fn may return a value, an error, or a value and an error. Byte slices are encoded as base64 by encoding/json.
+func Run(fn any) {
+	if err := run(fn, os.Args[1:]); err != nil {
+		fmt.Fprintln(os.Stderr, err)
+		os.Exit(1)
+	}
+}
+
+func run(fn any, args []string) error {
+	value := reflect.ValueOf(fn)
+	if value.Kind() != reflect.Func {
+		return errors.New("the node function is not a function")
+	}
+
+	fnType := value.Type()
+	in, err := decodeArgs(fnType, args)
+	if err != nil {
+		return err
+	}
+
+	out := value.Call(in)
+
+	if n := len(out); n > 0 && fnType.Out(n-1) == errorType {
+		if err, _ := out[n-1].Interface().(error); err != nil {
+			return err
+		}
+		out = out[:n-1]
+	}
+
+	var result any
+	switch len(out) {
+	case 0:
+		result = nil
+	case 1:
+		result = out[0].Interface()
+	default:
+		return errors.New("the node function may return at most one value besides an error")
+	}
+
+	encoded, err := json.Marshal(result)
+	if err != nil {
+		return fmt.Errorf("return value is not JSON serializable: %w", err)
+	}
+
+	resultPath := os.Getenv("SERAPH_RESULT_PATH")
+	if resultPath == "" {
+		resultPath = "/app/.seraph_result"
+	}
+
+	// Written to a file rather than stdout, so prints of the function do not mix with its result
+	return os.WriteFile(resultPath, encoded, 0o644)
+}
+
+func decodeArgs(fnType reflect.Type, args []string) ([]reflect.Value, error) {
+	numIn := fnType.NumIn()
+	if fnType.IsVariadic() && len(args) < numIn-1 || !fnType.IsVariadic() && len(args) != numIn {
+		return nil, fmt.Errorf("the node function takes %d arguments, got %d", numIn, len(args))
+	}
+
+	in := make([]reflect.Value, len(args))
+	for i, arg := range args {
+		var paramType reflect.Type
+		if fnType.IsVariadic() && i >= numIn-1 {
+			paramType = fnType.In(numIn - 1).Elem()
+		} else {
+			paramType = fnType.In(i)
+		}
+
+		param := reflect.New(paramType)
+		if err := json.Unmarshal([]byte(arg), param.Interface()); err != nil {
+			return nil, fmt.Errorf("argument %d: %w", i+1, err)
+		}
+		in[i] = param.Elem()
+	}
+
+	return in, nil
+}
@@ -0,0 +1,69 @@
+#!/usr/bin/env bash
+# Calls `function_name` from `<module_name>.go`, a file of `package main`, through a generated entrypoint.
+#
+# Usage: main.sh [--with <module>@<version>]... module_name function_name [json_args...]
+set -euo pipefail
+
+dependencies=()
+
+# Dependencies come first as `--with <module>` pairs, mirroring `uv run --with`
+while [[ "${1:-}" == "--with" ]]; do
+  if [[ $# -lt 2 ]]; then
+    echo "--with expects a module path" >&2
+    exit 1
+  fi
+  dependencies+=("$2")
+  shift 2
+done
+
+if [[ $# -lt 2 ]]; then
+  echo "should have 3 args: module_name, function_name, function_args" >&2
+  exit 1
+fi
+
+module_name="$1"
+function_name="$2"
+shift 2
+
+if [[ ! "$function_name" =~ ^[A-Za-z_][A-Za-z0-9_]*$ ]]; then
+  echo "$function_name is not a valid Go identifier" >&2
+  exit 1
+fi
+
+if [[ ! -f "$PWD/$module_name.go" ]]; then
+  echo "$module_name.go not found" >&2
+  exit 1
+fi
+
+# The image root is read-only in sandboxed runs, downloaded modules go to the writable home instead
+if [[ ! -w "$(go env GOPATH)" ]]; then
+  export GOPATH="$HOME/go"
+fi
+
+# The node code is built in place, together with the files of the working directory
+cat >go.mod <<GOMOD
+module seraph_node
+
+go 1.22
+
+require seraph/harness v0.0.0
+
+replace seraph/harness => /seraph/harness
+GOMOD
+
+cat >seraph_main.go <<GOMAIN
+package main
+
+import "seraph/harness"
+
+func main() {
+	harness.Run($function_name)
+}
+GOMAIN
+
+if [[ ${#dependencies[@]} -gt 0 ]]; then
+  # go output goes to stderr so stdout only holds what the function prints
+  go get "${dependencies[@]}" >&2
+fi
+
+exec go run . "$@"
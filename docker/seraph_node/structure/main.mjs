@@ -0,0 +1,112 @@
+import { execFileSync } from "node:child_process";
+import { existsSync, writeFileSync } from "node:fs";
+import path from "node:path";
+import { pathToFileURL } from "node:url";
+
+const RESULT_PATH = process.env.SERAPH_RESULT_PATH ?? "/app/.seraph_result";
+const DEPENDENCIES_PATH = process.env.SERAPH_DEPENDENCIES_PATH ?? "/app/.seraph_dependencies";
+
+function parseArgs(argv) {
+  const dependencies = [];
+
+  // Dependencies come first as `--with <package>` pairs, mirroring `uv run --with`
+  while (argv[0] === "--with") {
+    if (argv.length < 2) {
+      throw new Error("--with expects a package name");
+    }
+    dependencies.push(argv[1]);
+    argv = argv.slice(2);
+  }
+
+  if (argv.length < 2) {
+    throw new Error("should have 3 args: module_name, function_name, function_args");
+  }
+
+  const [moduleName, functionName, ...functionArgs] = argv;
+  return { dependencies, moduleName, functionName, functionArgs };
+}
+
+function npm(args) {
+  // npm output goes to stderr so stdout only holds what the function prints
+  execFileSync("npm", [...args, "--no-audit", "--no-fund", "--loglevel=error"], {
+    cwd: process.cwd(),
+    stdio: ["ignore", process.stderr, process.stderr],
+  });
+}
+
+function installDependencies(dependencies) {
+  // A project shipped with the node is installed first, exactly as locked when there is a lockfile
+  if (existsSync("package-lock.json")) {
+    npm(["ci"]);
+  } else if (existsSync("package.json")) {
+    npm(["install"]);
+  }
+
+  if (dependencies.length > 0) {
+    npm(["install", "--no-save", ...dependencies]);
+  }
+}
+
+function installedIn(directory) {
+  if (!existsSync(path.join(directory, "node_modules"))) {
+    return [];
+  }
+
+  // `npm ls` exits non-zero on extraneous packages, which every `--no-save` install is
+  let output;
+  try {
+    output = execFileSync("npm", ["ls", "--json", "--depth=0"], { cwd: directory, stdio: ["ignore", "pipe", "ignore"] });
+  } catch (error) {
+    output = error.stdout;
+  }
+
+  const installed = JSON.parse(output.toString() || "{}").dependencies ?? {};
+  return Object.entries(installed)
+    .filter(([, info]) => info.version)
+    .map(([name, info]) => `${name}@${info.version}`);
+}
+
+function resolvedDependencies() {
+  // Images with cached dependencies preinstall them in `/node_modules`
+  const installed = new Set([...installedIn(process.cwd()), ...installedIn("/")]);
+  return [...installed].sort();
+}
+
+function encode(result) {
+  if (result === undefined) {
+    return null;
+  }
+
+  if (result instanceof Uint8Array) {
+    return Buffer.from(result).toString("base64");
+  }
+
+  return result;
+}
+
+async function main() {
+  const { dependencies, moduleName, functionName, functionArgs } = parseArgs(process.argv.slice(2));
+  const parsedArgs = functionArgs.map((arg) => JSON.parse(arg));
+
+  installDependencies(dependencies);
+  writeFileSync(DEPENDENCIES_PATH, JSON.stringify(resolvedDependencies()));
+
+  // The node code is in the working directory, not next to this harness
+  const module = await import(pathToFileURL(path.join(process.cwd(), `${moduleName}.js`)).href);
+  const fn = module[functionName] ?? module.default?.[functionName];
+
+  if (typeof fn !== "function") {
+    throw new Error(`${functionName} is not a function`);
+  }
+
+  // Awaiting a plain value is a no-op, so sync and async functions are handled alike
+  const result = await fn(...parsedArgs);
+
+  // Written to a file rather than stdout, so logs of the function do not mix with its result
+  writeFileSync(RESULT_PATH, JSON.stringify(encode(result)));
+}
+
+main().catch((error) => {
+  console.error(error);
+  process.exit(1);
+});
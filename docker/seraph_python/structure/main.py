@@ -1,10 +1,36 @@
+import base64
 import importlib
+import importlib.metadata
 import inspect
 import json
+import os
 import sys
 from pathlib import Path
 
 BASE_DIR = Path(__file__).parent.absolute()
+RESULT_PATH = Path(os.environ.get("SERAPH_RESULT_PATH", "/app/.seraph_result"))
+DEPENDENCIES_PATH = Path(os.environ.get("SERAPH_DEPENDENCIES_PATH", "/app/.seraph_dependencies"))
+
+
+def encode(result: object) -> object:
+    if isinstance(result, (bytes, bytearray)):
+        return base64.b64encode(result).decode("ascii")
+
+    return result
+
+
+def resolved_dependencies() -> list[str]:
+    # Packages of the base interpreter, such as uv itself, are not dependencies of the node
+    base_prefix = Path(sys.base_prefix).resolve()
+    installed = set()
+
+    for distribution in importlib.metadata.distributions():
+        location = Path(distribution.locate_file("")).resolve()
+        if location.is_relative_to(base_prefix):
+            continue
+        installed.add(f"{distribution.metadata['Name']}=={distribution.version}")
+
+    return sorted(installed)
 
 
 def main() -> None:
@@ -18,6 +44,11 @@ def main() -> None:
     function_args: list[str] = args[2:]
     parsed_args = [json.loads(arg) for arg in function_args]
 
+    # uv installed the `--with` packages and the project of the working directory, if any, before starting us
+    DEPENDENCIES_PATH.write_text(json.dumps(resolved_dependencies()))
+
+    # The node code is in the working directory, not next to this harness
+    sys.path.insert(0, os.getcwd())
     module = importlib.import_module(name=module_name)
     function = getattr(module, function_name)
 
@@ -27,7 +58,8 @@ def main() -> None:
 
     result = function(*parsed_args)
 
-    print(result, end="")
+    # Written to a file rather than stdout, so prints of the function do not mix with its result
+    RESULT_PATH.write_text(json.dumps(encode(result)))
 
 
 if __name__ == "__main__":
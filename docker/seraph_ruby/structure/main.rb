@@ -0,0 +1,64 @@
+require "json"
+require "tmpdir"
+
+# Harness helpers live in their own namespace, node code defines its functions at the top level
+module Seraph
+  RESULT_PATH = ENV.fetch("SERAPH_RESULT_PATH", "/app/.seraph_result")
+
+  def self.parse_args(argv)
+    dependencies = []
+
+    # Dependencies come first as `--with <gem>` pairs, mirroring `uv run --with`
+    while argv.first == "--with"
+      raise ArgumentError, "--with expects a gem name" if argv.length < 2
+
+      dependencies << argv[1]
+      argv = argv.drop(2)
+    end
+
+    raise ArgumentError, "should have 3 args: module_name, function_name, function_args" if argv.length < 2
+
+    module_name, function_name, *function_args = argv
+    [dependencies, module_name, function_name, function_args]
+  end
+
+  def self.install_dependencies(dependencies)
+    return if dependencies.empty?
+
+    # Installed under the temp directory, the image root is read-only in sandboxed runs
+    gem_home = File.join(Dir.tmpdir, "gems")
+    ENV["GEM_HOME"] = gem_home
+    ENV["GEM_PATH"] = [gem_home, *Gem.path].join(File::PATH_SEPARATOR)
+    Gem.clear_paths
+
+    # gem output goes to stderr so stdout only holds what the function prints
+    system("gem", "install", "--no-document", "--install-dir", gem_home, *dependencies, out: $stderr, exception: true)
+  end
+
+  def self.encode(result)
+    # Binary strings are the Ruby equivalent of bytes
+    return [result].pack("m0") if result.is_a?(String) && result.encoding == Encoding::BINARY
+
+    result
+  end
+
+  def self.main
+    dependencies, module_name, function_name, function_args = parse_args(ARGV)
+    parsed_args = function_args.map { |arg| JSON.parse(arg) }
+
+    install_dependencies(dependencies)
+
+    # The node code is in the working directory, not next to this harness
+    require File.join(Dir.pwd, module_name)
+
+    receiver = TOPLEVEL_BINDING.receiver
+    raise NoMethodError, "#{function_name} is not a function" unless receiver.respond_to?(function_name, true)
+
+    result = receiver.send(function_name, *parsed_args)
+
+    # Written to a file rather than stdout, so prints of the function do not mix with its result
+    File.write(RESULT_PATH, JSON.generate(encode(result)))
+  end
+end
+
+Seraph.main
@@ -0,0 +1,118 @@
+#!/usr/bin/env bash
+# Calls `function_name` from `<module_name>.rs` through an entrypoint generated for `rust-script`.
+#
+# Each JSON argument is decoded into the type of the matching parameter, the function must be `pub` and return a
+# `serde::Serialize` value.
+#
+# Usage: main.sh [--output-type <type>] [--with <crate>[=<version>]]... module_name function_name [json_args...]
+set -euo pipefail
+
+RESULT_PATH="${SERAPH_RESULT_PATH:-/app/.seraph_result}"
+
+output_type="string"
+dependencies=()
+
+while [[ $# -gt 0 ]]; do
+  case "$1" in
+    --output-type)
+      output_type="$2"
+      shift 2
+      ;;
+    --with)
+      dependencies+=("$2")
+      shift 2
+      ;;
+    *)
+      break
+      ;;
+  esac
+done
+
+if [[ $# -lt 2 ]]; then
+  echo "should have 3 args: module_name, function_name, function_args" >&2
+  exit 1
+fi
+
+module_name="$1"
+function_name="$2"
+shift 2
+
+if [[ ! "$function_name" =~ ^[A-Za-z_][A-Za-z0-9_]*$ ]]; then
+  echo "$function_name is not a valid Rust identifier" >&2
+  exit 1
+fi
+
+# The image root is read-only in sandboxed runs, cargo needs a writable home for its locks and downloads
+cargo_home="${CARGO_HOME:-$HOME/.cargo}"
+if [[ ! -w "$cargo_home" ]]; then
+  writable_home="$(mktemp -d)"
+  cp -r "$cargo_home/registry" "$writable_home/"
+  export CARGO_HOME="$writable_home"
+fi
+
+# The crates of the entrypoint were fetched when the image was built, only extra dependencies need the network
+if [[ ${#dependencies[@]} -eq 0 ]]; then
+  export CARGO_NET_OFFLINE=true
+fi
+
+call_args=()
+for ((i = 0; i < $#; i++)); do
+  call_args+=("arg(&args, $i)")
+done
+
+entrypoint="$(mktemp -d)/seraph_main.rs"
+
+{
+  echo '//! ```cargo'
+  echo '//! [dependencies]'
+  echo '//! base64 = "0.22"'
+  echo '//! serde = "1"'
+  echo '//! serde_json = "1"'
+  for dependency in "${dependencies[@]}"; do
+    if [[ "$dependency" == *=* ]]; then
+      echo "//! ${dependency%%=*} = \"${dependency#*=}\""
+    else
+      echo "//! $dependency = \"*\""
+    fi
+  done
+  echo '//! ```'
+
+  cat <<RUST
+
+// The node code is in the working directory, not next to this entrypoint
+#[path = "$PWD/$module_name.rs"]
+#[allow(dead_code)]
+mod node;
+
+use base64::Engine;
+
+const OUTPUT_TYPE: &str = "$output_type";
+
+fn arg<T: serde::de::DeserializeOwned>(args: &[String], index: usize) -> T {
+    serde_json::from_str(&args[index]).unwrap_or_else(|err| panic!("argument {}: {}", index + 1, err))
+}
+
+fn encode(value: serde_json::Value) -> serde_json::Value {
+    // \`Vec<u8>\` serializes as an array of numbers, bytes are expected base64 encoded
+    if OUTPUT_TYPE != "bytes" {
+        return value;
+    }
+
+    match serde_json::from_value::<Vec<u8>>(value.clone()) {
+        Ok(bytes) => serde_json::Value::String(base64::engine::general_purpose::STANDARD.encode(bytes)),
+        Err(_) => value,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = node::$function_name($(IFS=,; echo "${call_args[*]}"));
+    let value = serde_json::to_value(&result).expect("return value is not JSON serializable");
+
+    // Written to a file rather than stdout, so prints of the function do not mix with its result
+    std::fs::write("$RESULT_PATH", serde_json::to_string(&encode(value)).unwrap()).expect("failed to write the result");
+}
+RUST
+} >"$entrypoint"
+
+exec rust-script "$entrypoint" "$@"
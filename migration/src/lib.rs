@@ -1,6 +1,27 @@
 mod m20220101_000001_create_table;
 mod m20250818_000002_create_code_result_table;
 mod m20250823_000003_add_missing_cascade_code_results;
+mod m20261018_000004_add_queue_columns_to_code_results;
+mod m20261018_000005_add_execution_timeouts;
+mod m20261018_000006_add_container_id_to_code_results;
+mod m20261018_000007_add_resource_limits;
+mod m20261018_000008_add_execution_profile;
+mod m20261018_000009_split_code_result_output;
+mod m20261018_000010_type_code_result_return_value;
+mod m20261018_000011_store_language_as_text;
+mod m20261018_000012_add_code_node_versions;
+mod m20261018_000013_index_code_results_by_node;
+mod m20261018_000014_add_internal_error_reason;
+mod m20261018_000015_add_code_node_files;
+mod m20261018_000016_add_node_dependencies;
+mod m20261018_000017_add_dependency_images;
+mod m20261018_000018_add_workflows;
+mod m20261018_000019_add_workflow_control_steps;
+mod m20261018_000020_add_retry_policies;
+mod m20261018_000021_add_schedules;
+mod m20261018_000022_add_code_node_version_files;
+mod m20261018_000023_add_dependencies_unavailable_reason;
+mod m20261018_000024_keep_results_of_deleted_workflow_runs;
 pub use sea_orm_migration::prelude::*;
 
 pub struct Migrator;
@@ -12,6 +33,27 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20250818_000002_create_code_result_table::Migration),
             Box::new(m20250823_000003_add_missing_cascade_code_results::Migration),
+            Box::new(m20261018_000004_add_queue_columns_to_code_results::Migration),
+            Box::new(m20261018_000005_add_execution_timeouts::Migration),
+            Box::new(m20261018_000006_add_container_id_to_code_results::Migration),
+            Box::new(m20261018_000007_add_resource_limits::Migration),
+            Box::new(m20261018_000008_add_execution_profile::Migration),
+            Box::new(m20261018_000009_split_code_result_output::Migration),
+            Box::new(m20261018_000010_type_code_result_return_value::Migration),
+            Box::new(m20261018_000011_store_language_as_text::Migration),
+            Box::new(m20261018_000012_add_code_node_versions::Migration),
+            Box::new(m20261018_000013_index_code_results_by_node::Migration),
+            Box::new(m20261018_000014_add_internal_error_reason::Migration),
+            Box::new(m20261018_000015_add_code_node_files::Migration),
+            Box::new(m20261018_000016_add_node_dependencies::Migration),
+            Box::new(m20261018_000017_add_dependency_images::Migration),
+            Box::new(m20261018_000018_add_workflows::Migration),
+            Box::new(m20261018_000019_add_workflow_control_steps::Migration),
+            Box::new(m20261018_000020_add_retry_policies::Migration),
+            Box::new(m20261018_000021_add_schedules::Migration),
+            Box::new(m20261018_000022_add_code_node_version_files::Migration),
+            Box::new(m20261018_000023_add_dependencies_unavailable_reason::Migration),
+            Box::new(m20261018_000024_keep_results_of_deleted_workflow_runs::Migration),
         ]
     }
 }
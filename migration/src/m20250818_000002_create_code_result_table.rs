@@ -1,11 +1,13 @@
 use sea_orm_migration::{
     prelude::{extension::postgres::Type, *},
+    schema::*,
     sea_orm::{ActiveEnum, Schema},
 };
 
-use seraph_backend::code_result::Entity as CodeResultEntity;
 use seraph_backend::enums::ResultStatus;
 
+use crate::m20220101_000001_create_table::CodeNodes;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -18,15 +20,44 @@ impl MigrationTrait for Migration {
         manager.create_type(schema.create_enum_from_active_enum::<ResultStatus>()).await?;
 
         // Create the `code_results` table
-        manager.create_table(schema.create_table_from_entity(CodeResultEntity)).await?;
+        // The columns are spelled out rather than derived from the entity, so later migrations can extend the table.
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeResults::Table)
+                    .if_not_exists()
+                    .col(pk_auto(CodeResults::Id))
+                    .col(uuid(CodeResults::TaskId).not_null())
+                    .col(integer(CodeResults::CodeNodeId).not_null())
+                    .col(ColumnDef::new(CodeResults::Status).custom(ResultStatus::name()).not_null())
+                    .col(string_null(CodeResults::Output))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-code_results-code_node_id")
+                            .from(CodeResults::Table, CodeResults::CodeNodeId)
+                            .to(CodeNodes::Table, CodeNodes::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
         Ok(())
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         // Drop the `code_results` table
-        manager.drop_table(Table::drop().table(CodeResultEntity).to_owned()).await?;
+        manager.drop_table(Table::drop().table(CodeResults::Table).to_owned()).await?;
         // Drop the `result_status` enum type
         manager.drop_type(Type::drop().name(ResultStatus::name()).to_owned()).await?;
         Ok(())
     }
 }
+
+#[derive(DeriveIden)]
+enum CodeResults {
+    Table,
+    Id,
+    TaskId,
+    CodeNodeId,
+    Status,
+    Output,
+}
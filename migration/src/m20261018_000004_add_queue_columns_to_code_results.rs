@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const STATUS_INDEX: &str = "idx-code_results-status-id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the columns needed to use `code_results` as a durable job queue.
+        // `if_not_exists` keeps this idempotent on fresh databases, where the table is created from the current entity.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeResultColumn::Args)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeResultColumn::Dependencies)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeResultColumn::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::StartedAt).timestamp_with_time_zone().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::FinishedAt).timestamp_with_time_zone().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::WorkerId).uuid().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::HeartbeatAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Workers claim the oldest pending row, so index on status and id.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name(STATUS_INDEX)
+                    .table(CodeResultEntity)
+                    .col(CodeResultColumn::Status)
+                    .col(CodeResultColumn::Id)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name(STATUS_INDEX).table(CodeResultEntity).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::Args)
+                    .drop_column(CodeResultColumn::Dependencies)
+                    .drop_column(CodeResultColumn::CreatedAt)
+                    .drop_column(CodeResultColumn::StartedAt)
+                    .drop_column(CodeResultColumn::FinishedAt)
+                    .drop_column(CodeResultColumn::WorkerId)
+                    .drop_column(CodeResultColumn::HeartbeatAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
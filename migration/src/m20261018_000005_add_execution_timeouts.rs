@@ -0,0 +1,69 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::ActiveEnum,
+};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::enums::ResultStatus;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the `timeout` value to the `code_result_status` enum type
+        manager
+            .alter_type(Type::alter().name(ResultStatus::name()).add_value(Alias::new("timeout")).if_not_exists())
+            .await?;
+
+        // Per-node default timeout
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::TimeoutSeconds).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Per-run timeout override
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::TimeoutSeconds).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::TimeoutSeconds)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .drop_column(CodeNodeColumn::TimeoutSeconds)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Postgres cannot drop a value from an enum type, mark timed out results as errors instead
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE code_results SET status = 'error' WHERE status = 'timeout'")
+            .await?;
+        Ok(())
+    }
+}
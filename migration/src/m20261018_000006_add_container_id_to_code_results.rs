@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Track the container of a running task so it can be stopped on cancellation
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ContainerId).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::ContainerId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::{ActiveEnum, Schema},
+};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::enums::ErrorReason;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let schema = Schema::new(manager.get_database_backend());
+
+        // Per-node container resource limits
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::MemoryLimitMb).big_integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::CpuMillis).big_integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::PidsLimit).big_integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::TmpfsSizeMb).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Create the `code_result_error_reason` enum type
+        manager.create_type(schema.create_enum_from_active_enum::<ErrorReason>()).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ErrorReason).custom(ErrorReason::name()).null())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::ErrorReason)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager.drop_type(Type::drop().name(ErrorReason::name()).to_owned()).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .drop_column(CodeNodeColumn::MemoryLimitMb)
+                    .drop_column(CodeNodeColumn::CpuMillis)
+                    .drop_column(CodeNodeColumn::PidsLimit)
+                    .drop_column(CodeNodeColumn::TmpfsSizeMb)
+                    .to_owned(),
+            )
+            .await
+    }
+}
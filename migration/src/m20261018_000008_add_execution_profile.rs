@@ -0,0 +1,59 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::{ActiveEnum, Schema},
+};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::enums::ExecutionProfile;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let schema = Schema::new(manager.get_database_backend());
+
+        // Create the `execution_profile` enum type
+        manager.create_type(schema.create_enum_from_active_enum::<ExecutionProfile>()).await?;
+
+        // Existing nodes keep running unrestricted, new nodes are sandboxed unless created as trusted
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeNodeColumn::ExecutionProfile)
+                            .custom(ExecutionProfile::name())
+                            .not_null()
+                            .default(Expr::val("trusted").as_enum(ExecutionProfile::name())),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .modify_column(
+                        ColumnDef::new(CodeNodeColumn::ExecutionProfile).default(Expr::val("sandboxed").as_enum(ExecutionProfile::name())),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .drop_column(CodeNodeColumn::ExecutionProfile)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager.drop_type(Type::drop().name(ExecutionProfile::name()).to_owned()).await
+    }
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The merged `output` of previous runs is kept as their stdout
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .rename_column(Alias::new("output"), CodeResultColumn::Stdout)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::Stderr).text().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ReturnValue).text().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ExitCode).big_integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::Stderr)
+                    .drop_column(CodeResultColumn::ReturnValue)
+                    .drop_column(CodeResultColumn::ExitCode)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .rename_column(CodeResultColumn::Stdout, Alias::new("output"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
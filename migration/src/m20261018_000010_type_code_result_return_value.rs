@@ -0,0 +1,63 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::ActiveEnum,
+};
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::enums::ErrorReason;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the `output_type_mismatch` value to the `code_result_error_reason` enum type
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(ErrorReason::name())
+                    .add_value(Alias::new("output_type_mismatch"))
+                    .if_not_exists(),
+            )
+            .await?;
+
+        // Return values are JSON now, previously stored raw values become JSON strings
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .modify_column(
+                        ColumnDef::new(CodeResultColumn::ReturnValue)
+                            .json_binary()
+                            .using(Expr::cust("to_jsonb(return_value)")),
+                    )
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ErrorMessage).text().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::ErrorMessage)
+                    .modify_column(
+                        ColumnDef::new(CodeResultColumn::ReturnValue)
+                            .text()
+                            .using(Expr::cust("return_value #>> '{}'")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Postgres cannot drop a value from an enum type, fall back to the generic reason
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE code_results SET error_reason = 'non_zero_exit' WHERE error_reason = 'output_type_mismatch'")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::{ActiveEnum, Schema},
+};
+
+use seraph_backend::enums::CodeLanguage;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Languages are runtime names from the registry, adding one no longer needs a new enum value
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE code_nodes ALTER COLUMN language TYPE varchar USING language::text")
+            .await?;
+
+        manager.drop_type(Type::drop().name(CodeLanguage::name()).to_owned()).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let schema = Schema::new(manager.get_database_backend());
+
+        manager.create_type(schema.create_enum_from_active_enum::<CodeLanguage>()).await?;
+
+        // Fails while nodes use a runtime that was never an enum value, those have to be removed first
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE code_nodes ALTER COLUMN language TYPE code_language USING language::code_language")
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::ActiveEnum};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::enums::OutputType;
+
+use crate::m20220101_000001_create_table::CodeNodes;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const VERSION_INDEX: &str = "idx-code_node_versions-code_node_id-version";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every revision of the runnable parts of a node
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeNodeVersions::Table)
+                    .if_not_exists()
+                    .col(pk_auto(CodeNodeVersions::Id))
+                    .col(integer(CodeNodeVersions::CodeNodeId).not_null())
+                    .col(integer(CodeNodeVersions::Version).not_null())
+                    .col(string(CodeNodeVersions::Name).not_null())
+                    .col(string(CodeNodeVersions::FunctionName).not_null())
+                    .col(text(CodeNodeVersions::Code).not_null())
+                    .col(string(CodeNodeVersions::OutputName).not_null())
+                    .col(ColumnDef::new(CodeNodeVersions::OutputType).custom(OutputType::name()).not_null())
+                    .col(string(CodeNodeVersions::Language).not_null())
+                    .col(
+                        ColumnDef::new(CodeNodeVersions::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-code_node_versions-code_node_id")
+                            .from(CodeNodeVersions::Table, CodeNodeVersions::CodeNodeId)
+                            .to(CodeNodes::Table, CodeNodes::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(VERSION_INDEX)
+                    .table(CodeNodeVersions::Table)
+                    .col(CodeNodeVersions::CodeNodeId)
+                    .col(CodeNodeVersions::Version)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Nodes could not be updated so far, their current code is their first version
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeNodeColumn::Version).integer().not_null().default(1))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::Version).integer().not_null().default(1))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO code_node_versions (code_node_id, version, name, function_name, code, output_name, output_type, language) \
+                 SELECT id, version, name, function_name, code, output_name, output_type, language FROM code_nodes",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(CodeResultEntity).drop_column(CodeResultColumn::Version).to_owned())
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(CodeNodeEntity).drop_column(CodeNodeColumn::Version).to_owned())
+            .await?;
+
+        manager.drop_table(Table::drop().table(CodeNodeVersions::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CodeNodeVersions {
+    Table,
+    Id,
+    CodeNodeId,
+    Version,
+    Name,
+    FunctionName,
+    Code,
+    OutputName,
+    OutputType,
+    Language,
+    CreatedAt,
+}
@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const NODE_INDEX: &str = "idx-code_results-code_node_id-id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Results are listed per node newest first, and node listings look up the latest result of each node.
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name(NODE_INDEX)
+                    .table(CodeResultEntity)
+                    .col(CodeResultColumn::CodeNodeId)
+                    .col(CodeResultColumn::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name(NODE_INDEX).table(CodeResultEntity).to_owned())
+            .await
+    }
+}
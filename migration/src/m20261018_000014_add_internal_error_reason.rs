@@ -0,0 +1,33 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::ActiveEnum,
+};
+
+use seraph_backend::enums::ErrorReason;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the `internal_error` value to the `code_result_error_reason` enum type
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(ErrorReason::name())
+                    .add_value(Alias::new("internal_error"))
+                    .if_not_exists(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres cannot drop a value from an enum type, fall back to the generic reason
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE code_results SET error_reason = 'non_zero_exit' WHERE error_reason = 'internal_error'")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20220101_000001_create_table::CodeNodes;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const PATH_INDEX: &str = "idx-code_node_files-code_node_id-path";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Helper modules, config and data files packed next to the main module of a node
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeNodeFiles::Table)
+                    .if_not_exists()
+                    .col(pk_auto(CodeNodeFiles::Id))
+                    .col(integer(CodeNodeFiles::CodeNodeId).not_null())
+                    .col(string(CodeNodeFiles::Path).not_null())
+                    .col(binary(CodeNodeFiles::Content).not_null())
+                    .col(big_integer(CodeNodeFiles::Size).not_null())
+                    .col(
+                        ColumnDef::new(CodeNodeFiles::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-code_node_files-code_node_id")
+                            .from(CodeNodeFiles::Table, CodeNodeFiles::CodeNodeId)
+                            .to(CodeNodes::Table, CodeNodes::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(PATH_INDEX)
+                    .table(CodeNodeFiles::Table)
+                    .col(CodeNodeFiles::CodeNodeId)
+                    .col(CodeNodeFiles::Path)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(CodeNodeFiles::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CodeNodeFiles {
+    Table,
+    Id,
+    CodeNodeId,
+    Path,
+    Content,
+    Size,
+    UpdatedAt,
+}
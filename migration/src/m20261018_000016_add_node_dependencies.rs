@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_node_versions::{Column as CodeNodeVersionColumn, Entity as CodeNodeVersionEntity};
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Dependencies declared on the node, and on each of its versions
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeNodeColumn::Dependencies)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeVersionEntity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeNodeVersionColumn::Dependencies)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Package versions a run actually used
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::ResolvedDependencies).json_binary().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::ResolvedDependencies)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeVersionEntity)
+                    .drop_column(CodeNodeVersionColumn::Dependencies)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(CodeNodeEntity).drop_column(CodeNodeColumn::Dependencies).to_owned())
+            .await
+    }
+}
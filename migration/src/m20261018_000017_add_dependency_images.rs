@@ -0,0 +1,52 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    schema::*,
+    sea_orm::{ActiveEnum, Schema},
+};
+
+use seraph_backend::dependency_images::{Column as DependencyImageColumn, Entity as DependencyImageEntity};
+use seraph_backend::enums::ImageStatus;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let schema = Schema::new(manager.get_database_backend());
+
+        // Create the `dependency_image_status` enum type
+        manager.create_type(schema.create_enum_from_active_enum::<ImageStatus>()).await?;
+
+        // Runtime images with the dependencies of runs preinstalled
+        manager
+            .create_table(
+                Table::create()
+                    .table(DependencyImageEntity)
+                    .if_not_exists()
+                    .col(pk_auto(DependencyImageColumn::Id))
+                    .col(string_uniq(DependencyImageColumn::CacheKey))
+                    .col(string(DependencyImageColumn::Image))
+                    .col(string(DependencyImageColumn::BaseImage))
+                    .col(string(DependencyImageColumn::Language))
+                    .col(json_binary(DependencyImageColumn::Dependencies))
+                    .col(ColumnDef::new(DependencyImageColumn::Status).custom(ImageStatus::name()).not_null())
+                    .col(text_null(DependencyImageColumn::ErrorMessage))
+                    .col(
+                        ColumnDef::new(DependencyImageColumn::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone(DependencyImageColumn::UpdatedAt))
+                    .col(timestamp_with_time_zone(DependencyImageColumn::LastUsedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(DependencyImageEntity).to_owned()).await?;
+        manager.drop_type(Type::drop().name(ImageStatus::name()).to_owned()).await
+    }
+}
@@ -0,0 +1,162 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::ActiveEnum};
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::enums::ResultStatus;
+use seraph_backend::workflow_edges::{Column as WorkflowEdgeColumn, Entity as WorkflowEdgeEntity};
+use seraph_backend::workflow_runs::{Column as WorkflowRunColumn, Entity as WorkflowRunEntity};
+use seraph_backend::workflows::{Column as WorkflowColumn, Entity as WorkflowEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const EDGE_INDEX: &str = "idx-workflow_edges-workflow_id-to_step-argument";
+const RUN_RESULTS_INDEX: &str = "idx-code_results-workflow_run_id";
+const RUN_RESULTS_KEY: &str = "fk-code_results-workflow_run_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Graphs of code nodes, their steps are kept as a JSONB array
+        manager
+            .create_table(
+                Table::create()
+                    .table(WorkflowEntity)
+                    .if_not_exists()
+                    .col(pk_auto(WorkflowColumn::Id))
+                    .col(string(WorkflowColumn::Name))
+                    .col(json_binary(WorkflowColumn::Steps))
+                    .col(
+                        ColumnDef::new(WorkflowColumn::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone(WorkflowColumn::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Edges pass the return value of a step to an argument of another
+        manager
+            .create_table(
+                Table::create()
+                    .table(WorkflowEdgeEntity)
+                    .if_not_exists()
+                    .col(pk_auto(WorkflowEdgeColumn::Id))
+                    .col(integer(WorkflowEdgeColumn::WorkflowId))
+                    .col(string(WorkflowEdgeColumn::FromStep))
+                    .col(string(WorkflowEdgeColumn::ToStep))
+                    .col(integer(WorkflowEdgeColumn::Argument))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-workflow_edges-workflow_id")
+                            .from(WorkflowEdgeEntity, WorkflowEdgeColumn::WorkflowId)
+                            .to(WorkflowEntity, WorkflowColumn::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(EDGE_INDEX)
+                    .table(WorkflowEdgeEntity)
+                    .col(WorkflowEdgeColumn::WorkflowId)
+                    .col(WorkflowEdgeColumn::ToStep)
+                    .col(WorkflowEdgeColumn::Argument)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Runs keep a copy of the graph they follow
+        manager
+            .create_table(
+                Table::create()
+                    .table(WorkflowRunEntity)
+                    .if_not_exists()
+                    .col(pk_auto(WorkflowRunColumn::Id))
+                    .col(uuid_uniq(WorkflowRunColumn::RunId))
+                    .col(integer(WorkflowRunColumn::WorkflowId))
+                    .col(ColumnDef::new(WorkflowRunColumn::Status).custom(ResultStatus::name()).not_null())
+                    .col(json_binary(WorkflowRunColumn::Graph))
+                    .col(text_null(WorkflowRunColumn::ErrorMessage))
+                    .col(
+                        ColumnDef::new(WorkflowRunColumn::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(WorkflowRunColumn::FinishedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-workflow_runs-workflow_id")
+                            .from(WorkflowRunEntity, WorkflowRunColumn::WorkflowId)
+                            .to(WorkflowEntity, WorkflowColumn::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Each step of a run records a result
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::WorkflowRunId).integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::Step).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(RUN_RESULTS_KEY)
+                    .from(CodeResultEntity, CodeResultColumn::WorkflowRunId)
+                    .to(WorkflowRunEntity, WorkflowRunColumn::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name(RUN_RESULTS_INDEX)
+                    .table(CodeResultEntity)
+                    .col(CodeResultColumn::WorkflowRunId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name(RUN_RESULTS_INDEX).table(CodeResultEntity).to_owned())
+            .await?;
+
+        manager
+            .drop_foreign_key(ForeignKey::drop().name(RUN_RESULTS_KEY).table(CodeResultEntity).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::WorkflowRunId)
+                    .drop_column(CodeResultColumn::Step)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager.drop_table(Table::drop().table(WorkflowRunEntity).to_owned()).await?;
+        manager.drop_table(Table::drop().table(WorkflowEdgeEntity).to_owned()).await?;
+        manager.drop_table(Table::drop().table(WorkflowEntity).to_owned()).await
+    }
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::workflow_edges::{Column as WorkflowEdgeColumn, Entity as WorkflowEdgeEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Edges from a condition step are followed on one of its branches
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WorkflowEdgeEntity)
+                    .add_column_if_not_exists(ColumnDef::new(WorkflowEdgeColumn::Branch).boolean().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Map steps record one result per element of their input
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::MapIndex).integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(CodeResultEntity).drop_column(CodeResultColumn::MapIndex).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WorkflowEdgeEntity)
+                    .drop_column(WorkflowEdgeColumn::Branch)
+                    .to_owned(),
+            )
+            .await
+    }
+}
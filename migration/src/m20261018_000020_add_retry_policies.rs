@@ -0,0 +1,103 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::ActiveEnum};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::code_result_attempts::{Column as CodeResultAttemptColumn, Entity as CodeResultAttemptEntity};
+use seraph_backend::enums::{ErrorReason, ResultStatus};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const ATTEMPT_INDEX: &str = "idx-code_result_attempts-code_result_id-attempt";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // An empty policy takes every default, which runs a task once
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeNodeEntity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CodeNodeColumn::RetryPolicy)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Retried tasks go back in the queue with a later attempt, claimable once their backoff has passed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::Attempt).integer().not_null().default(1))
+                    .add_column_if_not_exists(ColumnDef::new(CodeResultColumn::AvailableAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Every finished attempt of a task
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeResultAttemptEntity)
+                    .if_not_exists()
+                    .col(pk_auto(CodeResultAttemptColumn::Id))
+                    .col(integer(CodeResultAttemptColumn::CodeResultId))
+                    .col(integer(CodeResultAttemptColumn::Attempt))
+                    .col(ColumnDef::new(CodeResultAttemptColumn::Status).custom(ResultStatus::name()).not_null())
+                    .col(ColumnDef::new(CodeResultAttemptColumn::ErrorReason).custom(ErrorReason::name()).null())
+                    .col(text_null(CodeResultAttemptColumn::ErrorMessage))
+                    .col(big_integer_null(CodeResultAttemptColumn::ExitCode))
+                    .col(text_null(CodeResultAttemptColumn::Stdout))
+                    .col(text_null(CodeResultAttemptColumn::Stderr))
+                    .col(timestamp_with_time_zone_null(CodeResultAttemptColumn::StartedAt))
+                    .col(timestamp_with_time_zone(CodeResultAttemptColumn::FinishedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-code_result_attempts-code_result_id")
+                            .from(CodeResultAttemptEntity, CodeResultAttemptColumn::CodeResultId)
+                            .to(CodeResultEntity, CodeResultColumn::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(ATTEMPT_INDEX)
+                    .table(CodeResultAttemptEntity)
+                    .col(CodeResultAttemptColumn::CodeResultId)
+                    .col(CodeResultAttemptColumn::Attempt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CodeResultAttemptEntity).if_exists().to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CodeResultEntity)
+                    .drop_column(CodeResultColumn::Attempt)
+                    .drop_column(CodeResultColumn::AvailableAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(CodeNodeEntity).drop_column(CodeNodeColumn::RetryPolicy).to_owned())
+            .await
+    }
+}
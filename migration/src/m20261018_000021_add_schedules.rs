@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+use seraph_backend::schedules::{Column as ScheduleColumn, Entity as ScheduleEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const DUE_INDEX: &str = "idx-schedules-next_run_at";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cron schedules enqueuing runs of a code node
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScheduleEntity)
+                    .if_not_exists()
+                    .col(pk_auto(ScheduleColumn::Id))
+                    .col(string(ScheduleColumn::Name))
+                    .col(integer(ScheduleColumn::CodeNodeId))
+                    .col(integer_null(ScheduleColumn::Version))
+                    .col(string(ScheduleColumn::Cron))
+                    .col(string(ScheduleColumn::Timezone))
+                    .col(json_binary(ScheduleColumn::Args))
+                    .col(json_binary(ScheduleColumn::Dependencies))
+                    .col(integer_null(ScheduleColumn::TimeoutSeconds))
+                    .col(boolean(ScheduleColumn::Enabled))
+                    .col(integer(ScheduleColumn::MaxCatchUp))
+                    .col(timestamp_with_time_zone_null(ScheduleColumn::NextRunAt))
+                    .col(timestamp_with_time_zone_null(ScheduleColumn::LastRunAt))
+                    .col(uuid_null(ScheduleColumn::LastTaskId))
+                    .col(
+                        ColumnDef::new(ScheduleColumn::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone(ScheduleColumn::UpdatedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-schedules-code_node_id")
+                            .from(ScheduleEntity, ScheduleColumn::CodeNodeId)
+                            .to(CodeNodeEntity, CodeNodeColumn::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The scheduler looks for due schedules on every poll
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name(DUE_INDEX)
+                    .table(ScheduleEntity)
+                    .col(ScheduleColumn::NextRunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(ScheduleEntity).if_exists().to_owned()).await
+    }
+}
@@ -0,0 +1,70 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use seraph_backend::code_node_version_files::{Column as CodeNodeVersionFileColumn, Entity as CodeNodeVersionFileEntity};
+use seraph_backend::code_nodes::{Column as CodeNodeColumn, Entity as CodeNodeEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const PATH_INDEX: &str = "idx-code_node_version_files-code_node_id-version-path";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The files of a node at each of its revisions
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeNodeVersionFileEntity)
+                    .if_not_exists()
+                    .col(pk_auto(CodeNodeVersionFileColumn::Id))
+                    .col(integer(CodeNodeVersionFileColumn::CodeNodeId))
+                    .col(integer(CodeNodeVersionFileColumn::Version))
+                    .col(string(CodeNodeVersionFileColumn::Path))
+                    .col(binary(CodeNodeVersionFileColumn::Content))
+                    .col(big_integer(CodeNodeVersionFileColumn::Size))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-code_node_version_files-code_node_id")
+                            .from(CodeNodeVersionFileEntity, CodeNodeVersionFileColumn::CodeNodeId)
+                            .to(CodeNodeEntity, CodeNodeColumn::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Runs load the files of one revision
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name(PATH_INDEX)
+                    .table(CodeNodeVersionFileEntity)
+                    .col(CodeNodeVersionFileColumn::CodeNodeId)
+                    .col(CodeNodeVersionFileColumn::Version)
+                    .col(CodeNodeVersionFileColumn::Path)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Which files older revisions had is unknown, they keep running with the current ones as before
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO code_node_version_files (code_node_id, version, path, content, size) \
+                 SELECT files.code_node_id, versions.version, files.path, files.content, files.size \
+                 FROM code_node_files files JOIN code_node_versions versions ON versions.code_node_id = files.code_node_id",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CodeNodeVersionFileEntity).if_exists().to_owned())
+            .await
+    }
+}
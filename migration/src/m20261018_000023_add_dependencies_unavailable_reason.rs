@@ -0,0 +1,38 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    sea_orm::ActiveEnum,
+};
+
+use seraph_backend::enums::ErrorReason;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the `dependencies_unavailable` value to the `code_result_error_reason` enum type
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(ErrorReason::name())
+                    .add_value(Alias::new("dependencies_unavailable"))
+                    .if_not_exists(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres cannot drop a value from an enum type, fall back to the generic reason
+        for table in ["code_results", "code_result_attempts"] {
+            manager
+                .get_connection()
+                .execute_unprepared(&format!(
+                    "UPDATE {} SET error_reason = 'internal_error' WHERE error_reason = 'dependencies_unavailable'",
+                    table
+                ))
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+use seraph_backend::code_result::{Column as CodeResultColumn, Entity as CodeResultEntity};
+use seraph_backend::workflow_runs::{Column as WorkflowRunColumn, Entity as WorkflowRunEntity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const RUN_RESULTS_KEY: &str = "fk-code_results-workflow_run_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Results outlive the workflow runs they were part of, so the run history of a node survives deleting a workflow
+        replace_run_results_key(manager, ForeignKeyAction::SetNull).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        replace_run_results_key(manager, ForeignKeyAction::Cascade).await
+    }
+}
+
+async fn replace_run_results_key(manager: &SchemaManager<'_>, on_delete: ForeignKeyAction) -> Result<(), DbErr> {
+    manager
+        .drop_foreign_key(ForeignKey::drop().name(RUN_RESULTS_KEY).table(CodeResultEntity).to_owned())
+        .await?;
+
+    manager
+        .create_foreign_key(
+            ForeignKey::create()
+                .name(RUN_RESULTS_KEY)
+                .from(CodeResultEntity, CodeResultColumn::WorkflowRunId)
+                .to(WorkflowRunEntity, WorkflowRunColumn::Id)
+                .on_delete(on_delete)
+                .to_owned(),
+        )
+        .await
+}
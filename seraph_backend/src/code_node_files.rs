@@ -0,0 +1,70 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// An extra file of a code node, packed next to its main module so the code can import or read it.
+///
+/// These are the current files, every change records a new version of the node with a copy of them in
+/// `code_node_version_files`.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "code_node_files")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    pub code_node_id: i32,
+
+    /// Path relative to the working directory of the container.
+    pub path: String,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+    pub size: i64,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub updated_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-code_node_files-code_node_id",
+        belongs_to = "super::code_nodes::Entity",
+        from = "Column::CodeNodeId",
+        to = "super::code_nodes::Column::Id",
+        on_delete = "Cascade"
+    )]
+    CodeNode,
+}
+
+impl Related<super::code_nodes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeNode.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// File listing entry, without the content.
+#[derive(Clone, Debug, DerivePartialModel, sea_orm::FromQueryResult, Serialize)]
+#[sea_orm(entity = "Entity")]
+pub struct FileInfo {
+    pub path: String,
+    pub size: i64,
+    pub updated_at: TimeDateTimeWithTimeZone,
+}
+
+/// Checks that `path` stays inside the working directory once extracted.
+pub fn validate_path(path: &str) -> Result<(), String> {
+    if path.is_empty() || path.len() > 255 {
+        return Err("File path must be between 1 and 255 characters".to_string());
+    }
+
+    if path.starts_with('/') || path.contains('\\') || path.contains('\0') {
+        return Err(format!("Invalid file path: '{}'", path));
+    }
+
+    if path.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return Err(format!("File path must not contain empty, '.' or '..' segments: '{}'", path));
+    }
+
+    Ok(())
+}
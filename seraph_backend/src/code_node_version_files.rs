@@ -0,0 +1,63 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Query;
+use serde::{Deserialize, Serialize};
+
+use crate::code_node_files::{Column as CodeNodeFileColumn, Entity as CodeNodeFile};
+
+/// An extra file of a code node as it was at a revision, runs of that revision get these files.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "code_node_version_files")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    pub code_node_id: i32,
+    pub version: i32,
+
+    /// Path relative to the working directory of the container.
+    pub path: String,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+    pub size: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-code_node_version_files-code_node_id",
+        belongs_to = "super::code_nodes::Entity",
+        from = "Column::CodeNodeId",
+        to = "super::code_nodes::Column::Id",
+        on_delete = "Cascade"
+    )]
+    CodeNode,
+}
+
+impl Related<super::code_nodes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeNode.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Copies the current files of a node to its revision `version`, without loading their content.
+pub async fn snapshot<C: ConnectionTrait>(db: &C, code_node_id: i32, version: i32) -> Result<(), DbErr> {
+    let current = Query::select()
+        .column(CodeNodeFileColumn::CodeNodeId)
+        .expr(Expr::val(version))
+        .columns([CodeNodeFileColumn::Path, CodeNodeFileColumn::Content, CodeNodeFileColumn::Size])
+        .from(CodeNodeFile)
+        .and_where(CodeNodeFileColumn::CodeNodeId.eq(code_node_id))
+        .to_owned();
+
+    let insert = Query::insert()
+        .into_table(Entity)
+        .columns([Column::CodeNodeId, Column::Version, Column::Path, Column::Content, Column::Size])
+        .select_from(current)
+        .map_err(|err| DbErr::Custom(err.to_string()))?
+        .to_owned();
+
+    db.execute(db.get_database_backend().build(&insert)).await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::code_nodes::Model as CodeNode;
+use crate::code_result::StringList;
+use crate::enums::OutputType;
+use sea_orm::Set;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A revision of the runnable parts of a code node, written each time they change.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "code_node_versions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    pub code_node_id: i32,
+    pub version: i32,
+
+    pub name: String,
+    pub function_name: String,
+    pub code: String,
+    pub output_name: String,
+    pub output_type: OutputType,
+    pub language: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub dependencies: StringList,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-code_node_versions-code_node_id",
+        belongs_to = "super::code_nodes::Entity",
+        from = "Column::CodeNodeId",
+        to = "super::code_nodes::Column::Id",
+        on_delete = "Cascade"
+    )]
+    CodeNode,
+}
+
+impl Related<super::code_nodes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeNode.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl ActiveModel {
+    /// Snapshot of the current revision of `node`.
+    pub fn of(node: &CodeNode) -> Self {
+        Self {
+            code_node_id: Set(node.id),
+            version: Set(node.version),
+            name: Set(node.name.clone()),
+            function_name: Set(node.function_name.clone()),
+            code: Set(node.code.clone()),
+            output_name: Set(node.output_name.clone()),
+            output_type: Set(node.output_type.clone()),
+            language: Set(node.language.clone()),
+            dependencies: Set(node.dependencies.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Model {
+    /// `node` as it was at this revision, settings that are not versioned keep their current value.
+    pub fn apply_to(&self, node: CodeNode) -> CodeNode {
+        CodeNode {
+            version: self.version,
+            name: self.name.clone(),
+            function_name: self.function_name.clone(),
+            code: self.code.clone(),
+            output_name: self.output_name.clone(),
+            output_type: self.output_type.clone(),
+            language: self.language.clone(),
+            dependencies: self.dependencies.clone(),
+            ..node
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use crate::enums::{CodeLanguage, OutputType};
+use crate::code_node_version_files::Model as CodeNodeVersionFile;
+use crate::code_result::StringList;
+use crate::enums::{ExecutionProfile, OutputType};
+use crate::error::SeraphError;
+use crate::retry::RetryPolicy;
+use crate::runtimes::LanguageRuntime;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use tempfile::TempPath;
@@ -15,13 +20,43 @@ pub struct Model {
     pub code: String,
     pub output_name: String,
     pub output_type: OutputType,
-    pub language: CodeLanguage,
+    /// Name of the `LanguageRuntime` the code runs with.
+    pub language: String,
+
+    /// Default execution timeout for runs of this node, falls back to the server default when unset.
+    pub timeout_seconds: Option<i32>,
+
+    /// Resource limits of the container, unset values fall back to the server defaults.
+    pub memory_limit_mb: Option<i64>,
+    pub cpu_millis: Option<i64>,
+    pub pids_limit: Option<i64>,
+    pub tmpfs_size_mb: Option<i64>,
+
+    pub execution_profile: ExecutionProfile,
+
+    /// Packages installed for every run, per-run dependencies are merged on top, see `dependencies::merge`.
+    /// Projects with a lockfile ship it as a node file instead, e.g. `pyproject.toml` and `uv.lock`.
+    #[sea_orm(column_type = "JsonBinary")]
+    pub dependencies: StringList,
+
+    /// How failed runs are retried, applies to every version.
+    #[sea_orm(column_type = "JsonBinary")]
+    pub retry_policy: RetryPolicy,
+
+    /// Current revision of the runnable fields, every revision is kept in `code_node_versions`.
+    pub version: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::code_result::Entity")]
     CodeResults,
+    #[sea_orm(has_many = "super::code_node_versions::Entity")]
+    Versions,
+    #[sea_orm(has_many = "super::code_node_files::Entity")]
+    Files,
+    #[sea_orm(has_many = "super::schedules::Entity")]
+    Schedules,
 }
 
 impl Related<super::code_result::Entity> for Entity {
@@ -30,80 +65,89 @@ impl Related<super::code_result::Entity> for Entity {
     }
 }
 
+impl Related<super::code_node_versions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Versions.def()
+    }
+}
+
+impl Related<super::code_node_files::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Files.def()
+    }
+}
+
+impl Related<super::schedules::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Schedules.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
 
-pub fn alter_code(node: &Model) -> String {
+pub fn alter_code(node: &Model) -> Result<String, SeraphError> {
     use unescape::unescape;
 
-    let _deserialized_code: String = serde_json::from_str(&node.code).unwrap();
-    let unescaped_code = unescape(&_deserialized_code).unwrap();
+    let _deserialized_code: String =
+        serde_json::from_str(&node.code).map_err(|err| SeraphError::Internal(format!("Stored code is not valid JSON: {}", err)))?;
+    let unescaped_code =
+        unescape(&_deserialized_code).ok_or_else(|| SeraphError::Internal("Stored code contains an invalid escape sequence".to_string()))?;
     let code_content = unescaped_code.trim_matches(char::from(0));
 
-    return code_content.to_string();
+    Ok(code_content.to_string())
 }
 
-pub struct Command {
-    command: Vec<String>,
-}
-impl Command {
-    pub fn new() -> Self {
-        Self { command: vec![] }
-    }
+impl Model {
+    /// Packs the main module and the extra `files` of the node, in the layout they have in the working directory.
+    ///
+    /// Paths are prefixed with `directory`, empty or ending with `/`. The main module is added last, so it wins over
+    /// an extra file of the same path.
+    pub async fn to_tar(&self, runtime: &dyn LanguageRuntime, files: &[CodeNodeVersionFile], directory: &str) -> Result<TempPath, SeraphError> {
+        use std::collections::BTreeSet;
+        use tokio::io::AsyncWriteExt;
+        use tokio_tar as tar;
 
-    pub fn add(&mut self, arg: &str) -> &mut Self {
-        self.command.push(arg.to_string());
-        self
-    }
+        let altered_code = alter_code(self)?;
 
-    pub fn to_vec(&self) -> Vec<String> {
-        self.command.clone()
-    }
-}
+        let tar_path = tempfile::Builder::new().suffix(".tar").tempfile()?.into_temp_path();
+        let tar_file = tokio::fs::File::create(&tar_path).await?;
+        let mut tar_builder = tar::Builder::new(tar_file);
 
-impl Model {
-    pub fn get_command(&self, args: &Vec<String>, dependencies: Option<&Vec<String>>) -> Vec<String> {
-        match self.language {
-            CodeLanguage::Python => {
-                let mut command = Command::new();
-                command.add("uv").add("run").add("-q");
-
-                if let Some(deps) = dependencies {
-                    deps.into_iter().for_each(|dep| {
-                        command.add("--with").add(&dep);
-                    });
-                }
-
-                command.add("main.py").add(&self.name).add(&self.function_name);
-
-                args.into_iter().for_each(|arg| {
-                    command.add(&arg);
-                });
-
-                command.to_vec()
-            }
-            CodeLanguage::JavaScript => todo!(),
+        let entries: Vec<(String, &[u8])> = files
+            .iter()
+            .map(|file| (format!("{}{}", directory, file.path), file.content.as_slice()))
+            .chain([(format!("{}{}", directory, runtime.source_file(&self.name)), altered_code.as_bytes())])
+            .collect();
+
+        // Directories are listed explicitly, so they are writable by every user and a warm container can clear them
+        // between runs. Sorted, parents come before their children.
+        let directories: BTreeSet<String> = entries
+            .iter()
+            .flat_map(|(path, _)| path.match_indices('/').map(|(end, _)| path[..end].to_string()).collect::<Vec<_>>())
+            .collect();
+
+        for directory in directories {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_size(0);
+            header.set_mode(0o777);
+            header.set_mtime(0);
+            header.set_cksum();
+            tar_builder.append_data(&mut header, format!("{}/", directory), &[][..]).await?;
         }
-    }
 
-    pub async fn to_tar(&self) -> TempPath {
-        use tokio::fs::File;
-        use tokio_tar as tar;
+        // Files are readable by every user, sandboxed containers do not run as the owner.
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            header.set_cksum();
+            tar_builder.append_data(&mut header, path, content).await?;
+        }
 
-        let altered_code = alter_code(&self);
-        let file_path = tempfile::NamedTempFile::new().unwrap();
-        tokio::fs::write(&file_path, altered_code).await.expect("Failed to write code to file");
+        tar_builder.into_inner().await?.flush().await?;
 
-        let tar_path = tempfile::Builder::new().suffix(".tar").tempfile().unwrap().into_temp_path();
-        let tar_file = tokio::fs::File::create(&tar_path).await.unwrap();
-        let mut tar_builder = tar::Builder::new(tar_file);
-        tar_builder
-            .append_file(
-                format!("{}.{}", &self.name, &self.language.get_extension()),
-                &mut File::open(&file_path).await.unwrap(),
-            )
-            .await
-            .unwrap();
-
-        tar_path
+        Ok(tar_path)
     }
 }
@@ -1,7 +1,13 @@
-use crate::enums::ResultStatus;
+use crate::enums::{ErrorReason, ResultStatus};
+use sea_orm::FromJsonQueryResult;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// A list of strings persisted as a JSONB array, used for run arguments and dependencies.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, FromJsonQueryResult)]
+#[serde(transparent)]
+pub struct StringList(pub Vec<String>);
+
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
 #[sea_orm(table_name = "code_results")]
 pub struct Model {
@@ -10,8 +16,53 @@ pub struct Model {
     pub id: i32,
     pub task_id: uuid::Uuid,
     pub code_node_id: i32,
+    /// Revision of the code node this task runs.
+    pub version: i32,
     pub status: ResultStatus,
-    pub output: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    /// JSON encoded value returned by the node function, kept apart from anything it printed.
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub return_value: Option<Json>,
+    pub exit_code: Option<i64>,
+    pub error_message: Option<String>,
+    pub error_reason: Option<ErrorReason>,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub args: StringList,
+    /// Per-run dependencies, installed on top of the node's own.
+    #[sea_orm(column_type = "JsonBinary")]
+    pub dependencies: StringList,
+    /// Exact package versions the run used, as reported by the harness. Unset when the runtime does not report them.
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub resolved_dependencies: Option<StringList>,
+    /// Per-run timeout override, takes precedence over the node's own timeout.
+    pub timeout_seconds: Option<i32>,
+    /// Docker container executing the task, lets any process sharing the Docker host cancel it.
+    #[serde(skip)]
+    pub container_id: Option<String>,
+
+    /// Workflow run this task is a step of, `step` names the step.
+    pub workflow_run_id: Option<i32>,
+    pub step: Option<String>,
+    /// Element of the input array a map step ran this task for.
+    pub map_index: Option<i32>,
+
+    /// Attempt the task is on, starts at 1. Finished attempts are kept in `code_result_attempts`.
+    pub attempt: i32,
+    /// A retried task is not claimed before this time.
+    pub available_at: Option<TimeDateTimeWithTimeZone>,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+    pub started_at: Option<TimeDateTimeWithTimeZone>,
+    pub finished_at: Option<TimeDateTimeWithTimeZone>,
+
+    /// The worker process that claimed this task, used together with `heartbeat_at` to detect orphans.
+    #[serde(skip)]
+    pub worker_id: Option<uuid::Uuid>,
+    #[serde(skip)]
+    pub heartbeat_at: Option<TimeDateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,6 +75,16 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     CodeNode,
+    #[sea_orm(
+        fk_name = "fk-code_results-workflow_run_id",
+        belongs_to = "super::workflow_runs::Entity",
+        from = "Column::WorkflowRunId",
+        to = "super::workflow_runs::Column::Id",
+        on_delete = "SetNull"
+    )]
+    WorkflowRun,
+    #[sea_orm(has_many = "super::code_result_attempts::Entity")]
+    Attempts,
 }
 
 impl Related<super::code_nodes::Entity> for Entity {
@@ -32,4 +93,16 @@ impl Related<super::code_nodes::Entity> for Entity {
     }
 }
 
+impl Related<super::workflow_runs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WorkflowRun.def()
+    }
+}
+
+impl Related<super::code_result_attempts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attempts.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
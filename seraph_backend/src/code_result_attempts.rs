@@ -0,0 +1,46 @@
+use crate::enums::{ErrorReason, ResultStatus};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One attempt at running a task, the `code_result` itself holds the outcome of the last one.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "code_result_attempts")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub code_result_id: i32,
+    /// Starts at 1.
+    pub attempt: i32,
+
+    pub status: ResultStatus,
+    pub error_reason: Option<ErrorReason>,
+    pub error_message: Option<String>,
+    pub exit_code: Option<i64>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+
+    pub started_at: Option<TimeDateTimeWithTimeZone>,
+    pub finished_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-code_result_attempts-code_result_id",
+        belongs_to = "super::code_result::Entity",
+        from = "Column::CodeResultId",
+        to = "super::code_result::Column::Id",
+        on_delete = "Cascade"
+    )]
+    CodeResult,
+}
+
+impl Related<super::code_result::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeResult.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
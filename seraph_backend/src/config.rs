@@ -1,4 +1,8 @@
+use std::collections::HashMap;
 use std::env;
+use std::str::FromStr;
+
+use crate::runtimes::RuntimeRegistry;
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -10,6 +14,61 @@ pub struct Config {
     pub db_password: String,
     pub db_name: String,
     pub db_url: String,
+    pub worker_poll_interval_ms: u64,
+    pub worker_lease_seconds: u64,
+    /// Interval at which `scheduler` looks for due schedules.
+    pub scheduler_poll_interval_ms: u64,
+    pub worker_concurrency: usize,
+    pub worker_language_limits: HashMap<String, usize>,
+    pub max_queue_depth: u64,
+    /// Longest `wait` a run request can block for.
+    pub max_run_wait_seconds: u64,
+    pub default_timeout_seconds: u64,
+    pub default_memory_mb: i64,
+    pub max_memory_mb: i64,
+    pub default_cpu_millis: i64,
+    pub max_cpu_millis: i64,
+    pub default_pids_limit: i64,
+    pub max_pids_limit: i64,
+    pub default_tmpfs_mb: i64,
+    pub max_tmpfs_mb: i64,
+    /// Network attached to sandboxed containers, they get no network at all when unset.
+    pub sandbox_network: Option<String>,
+    pub sandbox_user: String,
+    /// Content of the seccomp profile applied to sandboxed containers, Docker's default profile is used when unset.
+    pub sandbox_seccomp_profile: Option<String>,
+    /// Largest extra file a code node can hold.
+    pub max_file_size_bytes: usize,
+    /// Number of dependency images kept by `image_cache`, 0 installs dependencies on every run instead.
+    pub dependency_image_cache_size: usize,
+    /// Idle pre-started containers kept per language by `pool::WarmPool`, languages left out run in fresh containers.
+    pub warm_pool_sizes: HashMap<String, usize>,
+    /// Runs after which a warm container is replaced.
+    pub warm_pool_max_runs: u32,
+    /// Built-in runtimes plus the ones declared in the file at `RUNTIMES_CONFIG`.
+    pub runtimes: RuntimeRegistry,
+}
+
+/// Reads an optional variable, falling back to `default` when it is unset.
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    match env::var(key) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("Invalid value for {}", key)),
+        Err(_) => default,
+    }
+}
+
+/// Parses per-language limits written as `python=4,javascript=2`, also used for warm pool sizes.
+fn parse_language_limits(value: &str) -> HashMap<String, usize> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (language, limit) = entry.split_once('=').unwrap_or_else(|| panic!("Invalid language limit: '{}'", entry));
+            let limit: usize = limit.trim().parse().expect("Invalid language limit");
+            (language.trim().to_lowercase(), limit)
+        })
+        .collect()
 }
 
 impl Config {
@@ -17,23 +76,54 @@ impl Config {
         dotenvy::dotenv().ok();
 
         let server_address = env::var("SERVER_ADDRESS").unwrap();
-        let server_port: u16 = env::var("SERVER_PORT")
-            .unwrap()
-            .parse()
-            .expect("Invalid server port");
+        let server_port: u16 = env::var("SERVER_PORT").unwrap().parse().expect("Invalid server port");
         let db_host = env::var("DATABASE_HOST").unwrap();
-        let db_port: u16 = env::var("DATABASE_PORT")
-            .unwrap()
-            .parse()
-            .expect("Invalid database port");
+        let db_port: u16 = env::var("DATABASE_PORT").unwrap().parse().expect("Invalid database port");
         let db_user = env::var("POSTGRES_USER").unwrap();
         let db_password = env::var("POSTGRES_PASSWORD").unwrap();
         let db_name = env::var("DATABASE_NAME").unwrap();
 
-        let db_url = format!(
-            "postgres://{}:{}@{}:{}/{}",
-            db_user, db_password, db_host, db_port, db_name
-        );
+        let db_url = format!("postgres://{}:{}@{}:{}/{}", db_user, db_password, db_host, db_port, db_name);
+
+        let worker_poll_interval_ms = env_or("WORKER_POLL_INTERVAL_MS", 1000);
+        let worker_lease_seconds = env_or("WORKER_LEASE_SECONDS", 60);
+        let scheduler_poll_interval_ms = env_or("SCHEDULER_POLL_INTERVAL_MS", 1000);
+        let worker_concurrency = env_or("WORKER_CONCURRENCY", 4);
+        let worker_language_limits = parse_language_limits(&env::var("WORKER_LANGUAGE_LIMITS").unwrap_or_default());
+        let max_queue_depth = env_or("MAX_QUEUE_DEPTH", 1000);
+        let max_run_wait_seconds = env_or("MAX_RUN_WAIT_SECONDS", 60);
+        let default_timeout_seconds = env_or("DEFAULT_TIMEOUT_SECONDS", 300);
+        let default_memory_mb = env_or("DEFAULT_MEMORY_MB", 256);
+        let max_memory_mb = env_or("MAX_MEMORY_MB", 2048);
+        let default_cpu_millis = env_or("DEFAULT_CPU_MILLIS", 1000);
+        let max_cpu_millis = env_or("MAX_CPU_MILLIS", 4000);
+        let default_pids_limit = env_or("DEFAULT_PIDS_LIMIT", 128);
+        let max_pids_limit = env_or("MAX_PIDS_LIMIT", 1024);
+        let default_tmpfs_mb = env_or("DEFAULT_TMPFS_MB", 64);
+        let max_tmpfs_mb = env_or("MAX_TMPFS_MB", 1024);
+        let sandbox_network = env::var("SANDBOX_NETWORK").ok().filter(|network| !network.is_empty());
+        let sandbox_user = env_or("SANDBOX_USER", "65534:65534".to_string());
+        let sandbox_seccomp_profile = env::var("SANDBOX_SECCOMP_PROFILE")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(|path| std::fs::read_to_string(path).expect("Failed to read seccomp profile"));
+        let max_file_size_bytes = env_or("MAX_FILE_SIZE_BYTES", 10 * 1024 * 1024);
+        let dependency_image_cache_size = env_or("DEPENDENCY_IMAGE_CACHE_SIZE", 20);
+        let warm_pool_sizes = parse_language_limits(&env::var("WARM_POOL_SIZES").unwrap_or_default());
+        let warm_pool_max_runs = env_or("WARM_POOL_MAX_RUNS", 50);
+        let runtimes = RuntimeRegistry::load(env::var("RUNTIMES_CONFIG").ok().filter(|path| !path.is_empty()).as_deref());
+
+        for language in worker_language_limits.keys() {
+            if runtimes.get(language).is_none() {
+                panic!("Unknown language in WORKER_LANGUAGE_LIMITS: '{}'", language);
+            }
+        }
+
+        for language in warm_pool_sizes.keys() {
+            if runtimes.get(language).is_none() {
+                panic!("Unknown language in WARM_POOL_SIZES: '{}'", language);
+            }
+        }
 
         Config {
             server_address,
@@ -44,6 +134,30 @@ impl Config {
             db_password,
             db_name,
             db_url,
+            worker_poll_interval_ms,
+            worker_lease_seconds,
+            scheduler_poll_interval_ms,
+            worker_concurrency,
+            worker_language_limits,
+            max_queue_depth,
+            max_run_wait_seconds,
+            default_timeout_seconds,
+            default_memory_mb,
+            max_memory_mb,
+            default_cpu_millis,
+            max_cpu_millis,
+            default_pids_limit,
+            max_pids_limit,
+            default_tmpfs_mb,
+            max_tmpfs_mb,
+            sandbox_network,
+            sandbox_user,
+            sandbox_seccomp_profile,
+            max_file_size_bytes,
+            dependency_image_cache_size,
+            warm_pool_sizes,
+            warm_pool_max_runs,
+            runtimes,
         }
     }
 }
@@ -0,0 +1,122 @@
+/// Name of the package a dependency specifier refers to, lowercased, e.g. `requests` for `Requests[socks]>=2.31`.
+///
+/// npm specifiers are `name@version`, where scoped packages start with `@`.
+pub fn package_name(specifier: &str) -> String {
+    let (scope, rest) = match specifier.strip_prefix('@') {
+        Some(rest) => ("@", rest),
+        None => ("", specifier),
+    };
+
+    let end = rest.find(|c: char| "=<>!~[;@ ".contains(c)).unwrap_or(rest.len());
+
+    format!("{}{}", scope, &rest[..end]).to_lowercase()
+}
+
+/// Dependencies of a run, the node's own list with per-run `extras` replacing entries of the same package.
+pub fn merge(node: &[String], extras: &[String]) -> Vec<String> {
+    let overridden: Vec<String> = extras.iter().map(|extra| package_name(extra)).collect();
+
+    node.iter()
+        .filter(|dependency| !overridden.contains(&package_name(dependency)))
+        .chain(extras)
+        .cloned()
+        .collect()
+}
+
+/// Checks dependency specifiers before they are handed to a package manager as arguments.
+pub fn validate(dependencies: &[String]) -> Result<(), String> {
+    for dependency in dependencies {
+        // A leading dash would be read as an option of the package manager.
+        // Control characters and backslashes would break out of a Dockerfile line, see `image_cache`.
+        let breaks_line = dependency.contains('\\') || dependency.chars().any(char::is_control);
+        if dependency.trim().is_empty() || dependency.starts_with('-') || breaks_line {
+            return Err(format!("Invalid dependency: '{}'", dependency));
+        }
+    }
+
+    Ok(())
+}
+
+/// Dependencies as single quoted shell words, for the `RUN` instructions of derived images.
+pub fn shell_words(dependencies: &[String]) -> String {
+    dependencies
+        .iter()
+        .map(|dependency| format!("'{}'", dependency.replace('\'', "'\\''")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn package_name_strips_versions_extras_and_markers() {
+        assert_eq!(package_name("Requests[socks]>=2.31"), "requests");
+        assert_eq!(package_name("numpy==1.26.4"), "numpy");
+        assert_eq!(package_name("pandas~=2.0"), "pandas");
+        assert_eq!(package_name("urllib3!=2.0.0"), "urllib3");
+        assert_eq!(package_name("tomli; python_version < '3.11'"), "tomli");
+        assert_eq!(package_name("tomli;python_version<'3.11'"), "tomli");
+        assert_eq!(package_name("httpx[http2,brotli] >= 0.27 ; sys_platform == 'linux'"), "httpx");
+        assert_eq!(package_name("rich"), "rich");
+    }
+
+    #[test]
+    fn package_name_keeps_npm_scopes() {
+        assert_eq!(package_name("lodash@^4.17.21"), "lodash");
+        assert_eq!(package_name("@types/node"), "@types/node");
+        assert_eq!(package_name("@types/node@20.11.0"), "@types/node");
+        assert_eq!(package_name("@Scope/Pkg@latest"), "@scope/pkg");
+    }
+
+    #[test]
+    fn merge_replaces_node_entries_of_the_same_package() {
+        let node = strings(&["requests==2.31.0", "numpy", "@types/node@18"]);
+        let extras = strings(&["Requests[socks]>=2.32", "@types/node@20"]);
+
+        assert_eq!(merge(&node, &extras), strings(&["numpy", "Requests[socks]>=2.32", "@types/node@20"]));
+    }
+
+    #[test]
+    fn merge_without_extras_keeps_the_node_list() {
+        let node = strings(&["numpy", "pandas>=2"]);
+
+        assert_eq!(merge(&node, &[]), node);
+        assert_eq!(merge(&[], &node), node);
+    }
+
+    #[test]
+    fn validate_accepts_extras_and_markers() {
+        let dependencies = strings(&[
+            "requests[socks]>=2.31",
+            "tomli; python_version < '3.11'",
+            "@types/node@^20",
+            "git+https://x/y.git",
+        ]);
+
+        assert!(validate(&dependencies).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_options_and_line_breaks() {
+        for dependency in ["", "   ", "-e .", "--index-url=https://x", "numpy\nRUN id", "numpy\r", "numpy \\", "a\tb"] {
+            assert!(validate(&strings(&[dependency])).is_err(), "accepted {:?}", dependency);
+        }
+    }
+
+    #[test]
+    fn shell_words_quotes_every_dependency() {
+        let dependencies = strings(&["numpy>=1.26", "tomli; python_version < '3.11'", "$(id)", "a b"]);
+
+        assert_eq!(
+            shell_words(&dependencies),
+            r#"'numpy>=1.26' 'tomli; python_version < '\''3.11'\''' '$(id)' 'a b'"#
+        );
+        assert_eq!(shell_words(&[]), "");
+    }
+}
@@ -0,0 +1,38 @@
+use crate::code_result::StringList;
+use crate::enums::ImageStatus;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A runtime image with a set of dependencies preinstalled, see `image_cache`.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "dependency_images")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+
+    /// Hash of the base image ID and the dependency set, see `image_cache::cache_key`.
+    #[sea_orm(unique)]
+    pub cache_key: String,
+    /// Tag of the derived image.
+    pub image: String,
+    pub base_image: String,
+    pub language: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub dependencies: StringList,
+
+    pub status: ImageStatus,
+    pub error_message: Option<String>,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+    /// Last status change, tells a build that is still running from one abandoned by a crashed process.
+    pub updated_at: TimeDateTimeWithTimeZone,
+    /// Least recently used images are evicted first.
+    pub last_used_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
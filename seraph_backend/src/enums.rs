@@ -3,7 +3,10 @@ use std::{fmt::Display, str::FromStr};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
+/// Values of the former `code_language` Postgres enum, kept for the migrations that create and drop it.
+///
+/// Nodes now store the name of a `LanguageRuntime` instead, see `runtimes::RuntimeRegistry`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "code_language")]
 #[serde(rename_all = "lowercase")]
 pub enum CodeLanguage {
@@ -34,22 +37,6 @@ impl Display for CodeLanguage {
     }
 }
 
-impl CodeLanguage {
-    pub fn get_extension(&self) -> &str {
-        match self {
-            CodeLanguage::Python => "py",
-            CodeLanguage::JavaScript => "js",
-        }
-    }
-
-    pub fn get_image_name(&self) -> &str {
-        match self {
-            CodeLanguage::Python => "seraph_python:latest",
-            CodeLanguage::JavaScript => "node:latest",
-        }
-    }
-}
-
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "output_type")]
 #[serde(rename_all = "lowercase")]
@@ -84,6 +71,30 @@ impl Display for OutputType {
     }
 }
 
+impl OutputType {
+    /// Checks a JSON encoded return value against the declared type, `Bytes` are expected base64 encoded.
+    pub fn validate(&self, value: &serde_json::Value) -> Result<(), String> {
+        use base64::Engine;
+        use serde_json::Value;
+
+        let valid = match (self, value) {
+            (OutputType::String, Value::String(_)) => true,
+            (OutputType::Bytes, Value::String(encoded)) => base64::engine::general_purpose::STANDARD.decode(encoded).is_ok(),
+            (OutputType::Number, Value::Number(_)) => true,
+            (OutputType::Boolean, Value::Bool(_)) => true,
+            (OutputType::Array, Value::Array(_)) => true,
+            (OutputType::Object, Value::Object(_)) => true,
+            (OutputType::NoOutput, Value::Null) => true,
+            _ => false,
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(format!("Expected a {} return value, got {}", self, value)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "code_result_status")]
 #[serde(rename_all = "lowercase")]
@@ -98,6 +109,15 @@ pub enum ResultStatus {
     Running,
     #[sea_orm(string_value = "cancelled")]
     Cancelled,
+    #[sea_orm(string_value = "timeout")]
+    Timeout,
+}
+
+impl ResultStatus {
+    /// Whether a task with this status is done, a task that is retried goes back to `Pending`.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, ResultStatus::Pending | ResultStatus::Running)
+    }
 }
 
 impl Display for ResultStatus {
@@ -108,6 +128,82 @@ impl Display for ResultStatus {
             ResultStatus::Pending => write!(f, "pending"),
             ResultStatus::Running => write!(f, "running"),
             ResultStatus::Cancelled => write!(f, "cancelled"),
+            ResultStatus::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// Why a task ended with `ResultStatus::Error`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "code_result_error_reason")]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorReason {
+    #[sea_orm(string_value = "non_zero_exit")]
+    NonZeroExit,
+    #[sea_orm(string_value = "out_of_memory")]
+    OutOfMemory,
+    #[sea_orm(string_value = "output_type_mismatch")]
+    OutputTypeMismatch,
+    /// Seraph itself failed to run the task, e.g. Docker or the database was unavailable.
+    #[sea_orm(string_value = "internal_error")]
+    InternalError,
+    /// The dependencies cannot be installed: the run has no network and no image has them preinstalled.
+    #[sea_orm(string_value = "dependencies_unavailable")]
+    DependenciesUnavailable,
+}
+
+impl Display for ErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorReason::NonZeroExit => write!(f, "non_zero_exit"),
+            ErrorReason::OutOfMemory => write!(f, "out_of_memory"),
+            ErrorReason::OutputTypeMismatch => write!(f, "output_type_mismatch"),
+            ErrorReason::InternalError => write!(f, "internal_error"),
+            ErrorReason::DependenciesUnavailable => write!(f, "dependencies_unavailable"),
+        }
+    }
+}
+
+/// How much a code node is trusted, decides the isolation applied to its containers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "execution_profile")]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionProfile {
+    #[default]
+    #[sea_orm(string_value = "sandboxed")]
+    Sandboxed,
+    #[sea_orm(string_value = "trusted")]
+    Trusted,
+}
+
+impl Display for ExecutionProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionProfile::Sandboxed => write!(f, "sandboxed"),
+            ExecutionProfile::Trusted => write!(f, "trusted"),
+        }
+    }
+}
+
+/// Build state of a cached dependency image.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, DeriveActiveEnum, EnumIter)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "dependency_image_status")]
+#[serde(rename_all = "lowercase")]
+pub enum ImageStatus {
+    #[sea_orm(string_value = "building")]
+    Building,
+    #[sea_orm(string_value = "ready")]
+    Ready,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+impl Display for ImageStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageStatus::Building => write!(f, "building"),
+            ImageStatus::Ready => write!(f, "ready"),
+            ImageStatus::Failed => write!(f, "failed"),
         }
     }
 }
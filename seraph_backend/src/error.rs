@@ -0,0 +1,116 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use sea_orm::DbErr;
+use serde_json::{Value, json};
+
+use crate::enums::{ErrorReason, ResultStatus};
+
+/// Errors of the API handlers and of task execution.
+#[derive(Debug, thiserror::Error)]
+pub enum SeraphError {
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Task queue is full")]
+    QueueFull { retry_after: u64 },
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+    #[error("Docker error: {0}")]
+    Docker(#[from] bollard::errors::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Internal(String),
+    /// A run waited for with `?wait` did not succeed.
+    #[error("Run ended with status {status}")]
+    RunFailed {
+        task_id: uuid::Uuid,
+        status: ResultStatus,
+        error_reason: Option<ErrorReason>,
+        error_message: Option<String>,
+        exit_code: Option<i64>,
+    },
+}
+
+impl SeraphError {
+    /// Stable identifier of the error kind, for clients to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SeraphError::NotFound(_) => "not_found",
+            SeraphError::InvalidInput(_) => "invalid_input",
+            SeraphError::Conflict(_) => "conflict",
+            SeraphError::QueueFull { .. } => "queue_full",
+            SeraphError::Database(_) => "database_error",
+            SeraphError::Docker(_) => "docker_error",
+            SeraphError::Io(_) => "io_error",
+            SeraphError::Internal(_) => "internal_error",
+            SeraphError::RunFailed { .. } => "run_failed",
+        }
+    }
+
+    /// Message returned to API clients, server side failures only reveal their kind.
+    fn public_message(&self) -> String {
+        match self {
+            SeraphError::Database(_) => "Database error".to_string(),
+            SeraphError::Docker(_) => "Docker error".to_string(),
+            SeraphError::Io(_) | SeraphError::Internal(_) => "Internal server error".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            SeraphError::NotFound(resource) => json!({ "resource": resource }),
+            SeraphError::QueueFull { retry_after } => json!({ "retry_after": retry_after }),
+            SeraphError::RunFailed {
+                task_id,
+                status,
+                error_reason,
+                error_message,
+                exit_code,
+            } => json!({
+                "task_id": task_id,
+                "status": status,
+                "error_reason": error_reason,
+                "error_message": error_message,
+                "exit_code": exit_code,
+            }),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl ResponseError for SeraphError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SeraphError::NotFound(_) => StatusCode::NOT_FOUND,
+            SeraphError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            SeraphError::Conflict(_) => StatusCode::CONFLICT,
+            SeraphError::QueueFull { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            SeraphError::RunFailed { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            SeraphError::Database(_) | SeraphError::Docker(_) | SeraphError::Io(_) | SeraphError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() && !matches!(self, SeraphError::QueueFull { .. }) {
+            tracing::error!("{}", self);
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let SeraphError::QueueFull { retry_after } = self {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+
+        response.json(json!({
+            "code": self.code(),
+            "message": self.public_message(),
+            "details": self.details(),
+        }))
+    }
+}
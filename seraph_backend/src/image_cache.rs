@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bollard::Docker;
+use bollard::body_full;
+use bollard::errors::Error as DockerError;
+use bollard::query_parameters::{BuildImageOptions, RemoveImageOptions};
+use futures_util::StreamExt;
+use sea_orm::prelude::TimeDateTimeWithTimeZone;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, QuerySelect, Set};
+use sha2::{Digest, Sha256};
+
+use crate::code_result::StringList;
+use crate::config::Config;
+use crate::dependency_images::{ActiveModel as DependencyImageActiveModel, Column as DependencyImageColumn, Entity as DependencyImage, Model};
+use crate::enums::ImageStatus;
+use crate::error::SeraphError;
+use crate::runtimes::LanguageRuntime;
+
+/// Repository of the derived images, tagged with their cache key.
+const IMAGE_REPOSITORY: &str = "seraph_deps";
+
+/// A build that has not finished after this long was abandoned by a crashed process and can be taken over.
+const BUILD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// State of the cache entry of a dependency set.
+pub enum Entry {
+    /// The image exists and can be run.
+    Ready(Model),
+    /// The caller claimed the entry and must build it with `build`.
+    Claimed(Model, Vec<String>),
+    /// Another process is building the image.
+    Building(Model),
+    /// The last build failed, only `POST /code-node/{id}/prepare` retries it.
+    Failed(Model),
+}
+
+/// Identifies the image of `dependencies` on top of the base image `base_image_id`, whatever their order.
+pub fn cache_key(base_image_id: &str, dependencies: &[String]) -> String {
+    let mut dependencies = dependencies.to_vec();
+    dependencies.sort();
+    dependencies.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(base_image_id.as_bytes());
+    for dependency in &dependencies {
+        hasher.update(b"\n");
+        hasher.update(dependency.as_bytes());
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Dockerfile instructions of the derived image, `None` when `dependencies` are installed on every run instead.
+fn image_setup(config: &Config, runtime: &dyn LanguageRuntime, dependencies: &[String]) -> Option<Vec<String>> {
+    if config.dependency_image_cache_size == 0 || dependencies.is_empty() {
+        return None;
+    }
+
+    runtime.image_setup(dependencies)
+}
+
+/// Where the dependencies of a run come from, see `resolve`.
+pub enum Resolution {
+    /// Preinstalled in this image.
+    Image(String),
+    /// Another process is building their image.
+    Building,
+    /// Installed by the harness on every run: caching is disabled, the runtime cannot preinstall them, or the last
+    /// build of their image failed.
+    Install,
+}
+
+/// Image to run `dependencies` in, or why the harness has to install them itself.
+///
+/// A missing image is built first. `POST /code-node/{id}/prepare` is the only way to retry a failed build.
+pub async fn resolve(
+    db: &DatabaseConnection,
+    docker: &Docker,
+    config: &Config,
+    runtime: &dyn LanguageRuntime,
+    dependencies: &[String],
+) -> Result<Resolution, SeraphError> {
+    match lookup(db, docker, config, runtime, dependencies, false).await? {
+        Some(Entry::Ready(entry)) => Ok(Resolution::Image(entry.image)),
+        Some(Entry::Claimed(entry, setup)) => match build(db, docker, entry, setup, config.dependency_image_cache_size).await {
+            Ok(entry) => Ok(Resolution::Image(entry.image)),
+            Err(err) => {
+                tracing::warn!("Building the image of dependencies failed: {}", err);
+                Ok(Resolution::Install)
+            }
+        },
+        Some(Entry::Building(_)) => Ok(Resolution::Building),
+        Some(Entry::Failed(_)) | None => Ok(Resolution::Install),
+    }
+}
+
+/// Cache entry of `dependencies`, claimed for building when the image is missing.
+///
+/// `retry_failed` claims entries whose last build failed too. `None` when the runtime cannot preinstall dependencies
+/// or caching is disabled.
+pub async fn lookup(
+    db: &DatabaseConnection,
+    docker: &Docker,
+    config: &Config,
+    runtime: &dyn LanguageRuntime,
+    dependencies: &[String],
+    retry_failed: bool,
+) -> Result<Option<Entry>, SeraphError> {
+    let Some(setup) = image_setup(config, runtime, dependencies) else {
+        return Ok(None);
+    };
+
+    // Keyed by the image ID rather than its name, so rebuilding the runtime image invalidates its derived images.
+    let base_image_id = docker
+        .inspect_image(runtime.image())
+        .await?
+        .id
+        .unwrap_or_else(|| runtime.image().to_string());
+    let key = cache_key(&base_image_id, dependencies);
+    let now = TimeDateTimeWithTimeZone::now_utc();
+
+    let entry = DependencyImageActiveModel {
+        cache_key: Set(key.clone()),
+        image: Set(format!("{}:{}", IMAGE_REPOSITORY, key)),
+        base_image: Set(runtime.image().to_string()),
+        language: Set(runtime.name().to_string()),
+        dependencies: Set(StringList(dependencies.to_vec())),
+        status: Set(ImageStatus::Building),
+        updated_at: Set(now),
+        last_used_at: Set(now),
+        ..Default::default()
+    };
+
+    let inserted = DependencyImage::insert(entry)
+        .on_conflict(OnConflict::column(DependencyImageColumn::CacheKey).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
+
+    let entry = DependencyImage::find()
+        .filter(DependencyImageColumn::CacheKey.eq(&key))
+        .one(db)
+        .await?
+        .ok_or_else(|| SeraphError::Internal(format!("Dependency image {} vanished", key)))?;
+
+    if inserted > 0 {
+        return Ok(Some(Entry::Claimed(entry, setup)));
+    }
+
+    let abandoned = entry.updated_at < now - BUILD_TIMEOUT;
+    let entry = match entry.status {
+        ImageStatus::Ready => match docker.inspect_image(&entry.image).await {
+            Ok(_) => {
+                let mut used = entry.clone().into_active_model();
+                used.last_used_at = Set(now);
+                Entry::Ready(used.update(db).await?)
+            }
+            // Removed behind our back, e.g. by `docker image prune`.
+            Err(DockerError::DockerResponseServerError { status_code: 404, .. }) => take_over(db, entry, setup).await?,
+            Err(err) => return Err(err.into()),
+        },
+        ImageStatus::Building if abandoned => take_over(db, entry, setup).await?,
+        ImageStatus::Building => Entry::Building(entry),
+        ImageStatus::Failed if retry_failed => take_over(db, entry, setup).await?,
+        ImageStatus::Failed => Entry::Failed(entry),
+    };
+
+    Ok(Some(entry))
+}
+
+/// Claims `entry` for building, unless another process changed it since it was read.
+async fn take_over(db: &DatabaseConnection, entry: Model, setup: Vec<String>) -> Result<Entry, SeraphError> {
+    let now = TimeDateTimeWithTimeZone::now_utc();
+
+    let updated = DependencyImage::update_many()
+        .col_expr(
+            DependencyImageColumn::Status,
+            DependencyImageColumn::Status.save_as(Expr::val(ImageStatus::Building)),
+        )
+        .col_expr(DependencyImageColumn::UpdatedAt, now.into())
+        .col_expr(DependencyImageColumn::ErrorMessage, Option::<String>::None.into())
+        .filter(DependencyImageColumn::Id.eq(entry.id))
+        .filter(DependencyImageColumn::UpdatedAt.eq(entry.updated_at))
+        .exec(db)
+        .await?;
+
+    let entry = Model {
+        status: ImageStatus::Building,
+        updated_at: now,
+        error_message: None,
+        ..entry
+    };
+
+    match updated.rows_affected {
+        0 => Ok(Entry::Building(entry)),
+        _ => Ok(Entry::Claimed(entry, setup)),
+    }
+}
+
+/// Builds the image of a claimed entry and evicts the least recently used images beyond `cache_size`.
+pub async fn build(db: &DatabaseConnection, docker: &Docker, entry: Model, setup: Vec<String>, cache_size: usize) -> Result<Model, SeraphError> {
+    tracing::info!("Building dependency image {} for {:?}", entry.image, entry.dependencies.0);
+
+    let built = build_image(docker, &entry, &setup).await;
+
+    let mut finished = entry.into_active_model();
+    finished.updated_at = Set(TimeDateTimeWithTimeZone::now_utc());
+    match &built {
+        Ok(()) => finished.status = Set(ImageStatus::Ready),
+        Err(err) => {
+            finished.status = Set(ImageStatus::Failed);
+            finished.error_message = Set(Some(err.to_string()));
+        }
+    }
+    let finished = finished.update(db).await?;
+
+    built?;
+
+    if let Err(err) = evict(db, docker, cache_size).await {
+        tracing::error!("Failed to evict dependency images: {}", err);
+    }
+
+    Ok(finished)
+}
+
+async fn build_image(docker: &Docker, entry: &Model, setup: &[String]) -> Result<(), SeraphError> {
+    use tokio_tar as tar;
+
+    let dockerfile = format!("FROM {}\n{}\n", entry.base_image, setup.join("\n"));
+
+    // The build context only holds the Dockerfile.
+    let mut context = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(dockerfile.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    context.append_data(&mut header, "Dockerfile", dockerfile.as_bytes()).await?;
+    let context = context.into_inner().await?;
+
+    let options = BuildImageOptions {
+        dockerfile: "Dockerfile".to_string(),
+        t: Some(entry.image.clone()),
+        rm: true,
+        forcerm: true,
+        labels: Some(HashMap::from([("seraph.cache_key".to_string(), entry.cache_key.clone())])),
+        ..Default::default()
+    };
+
+    let mut output = docker.build_image(options, None, Some(body_full(context.into())));
+    while let Some(info) = output.next().await {
+        let info = info?;
+
+        if let Some(message) = info.error_detail.and_then(|detail| detail.message) {
+            return Err(SeraphError::Internal(format!("Failed to build {}: {}", entry.image, message)));
+        }
+        if let Some(line) = info.stream {
+            tracing::debug!("{}: {}", entry.image, line.trim_end());
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the least recently used images beyond `cache_size`, images used by a container are kept for now.
+async fn evict(db: &DatabaseConnection, docker: &Docker, cache_size: usize) -> Result<(), SeraphError> {
+    let stale = DependencyImage::find()
+        .filter(DependencyImageColumn::Status.eq(ImageStatus::Ready))
+        .order_by_desc(DependencyImageColumn::LastUsedAt)
+        .offset(cache_size as u64)
+        .all(db)
+        .await?;
+
+    for entry in stale {
+        match docker.remove_image(&entry.image, None::<RemoveImageOptions>, None).await {
+            Ok(_) | Err(DockerError::DockerResponseServerError { status_code: 404, .. }) => {
+                tracing::info!("Evicted dependency image {}", entry.image);
+                entry.into_active_model().delete(db).await?;
+            }
+            Err(err) => tracing::warn!("Failed to evict dependency image {}: {}", entry.image, err),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_ignores_order_and_duplicates() {
+        let key = cache_key("sha256:base", &["numpy", "pandas>=2", "requests"].map(String::from));
+
+        assert_eq!(key, cache_key("sha256:base", &["requests", "numpy", "pandas>=2"].map(String::from)));
+        assert_eq!(
+            key,
+            cache_key("sha256:base", &["pandas>=2", "numpy", "requests", "numpy"].map(String::from))
+        );
+    }
+
+    #[test]
+    fn cache_key_depends_on_the_base_image_and_the_dependencies() {
+        let key = cache_key("sha256:base", &["numpy"].map(String::from));
+
+        assert_ne!(key, cache_key("sha256:other", &["numpy"].map(String::from)));
+        assert_ne!(key, cache_key("sha256:base", &["numpy==1.26.4"].map(String::from)));
+        assert_ne!(key, cache_key("sha256:base", &[]));
+    }
+
+    #[test]
+    fn cache_key_does_not_merge_adjacent_dependencies() {
+        assert_ne!(
+            cache_key("sha256:base", &["ab", "c"].map(String::from)),
+            cache_key("sha256:base", &["a", "bc"].map(String::from))
+        );
+    }
+}
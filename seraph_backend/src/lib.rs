@@ -1,6 +1,26 @@
+pub mod code_node_files;
+pub mod code_node_version_files;
+pub mod code_node_versions;
 pub mod code_nodes;
 pub mod config;
+pub mod dependencies;
+pub mod dependency_images;
 pub mod enums;
+pub mod error;
+pub mod image_cache;
+pub mod pool;
+pub mod queue;
+pub mod resources;
+pub mod retry;
+pub mod runtimes;
+pub mod sandbox;
+pub mod scheduler;
+pub mod schedules;
 pub mod server;
 pub mod code_result;
-pub mod worker;
\ No newline at end of file
+pub mod code_result_attempts;
+pub mod worker;
+pub mod workflow_edges;
+pub mod workflow_engine;
+pub mod workflow_runs;
+pub mod workflows;
\ No newline at end of file
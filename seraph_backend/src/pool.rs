@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bollard::Docker;
+use bollard::models::ContainerCreateBody;
+use bollard::query_parameters::{CreateContainerOptions, InspectContainerOptions, ListContainersOptions, StartContainerOptions};
+use sea_orm::DatabaseConnection;
+
+use crate::config::Config;
+use crate::enums::ExecutionProfile;
+use crate::error::SeraphError;
+use crate::queue;
+use crate::resources::{RequestedLimits, ResourceLimits};
+use crate::runtimes::LanguageRuntime;
+use crate::sandbox;
+use crate::worker::remove_container;
+
+/// Label of warm containers, set to the ID of the worker that started them.
+const POOL_LABEL: &str = "seraph.pool";
+
+/// Settings a warm container was created with, only runs resolving to the same ones can use it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub language: String,
+    pub image: String,
+    pub profile: ExecutionProfile,
+    pub limits: ResourceLimits,
+    /// Node the container is kept for once it ran code, `None` for fresh containers and trusted runs.
+    pub code_node_id: Option<i32>,
+}
+
+impl PoolKey {
+    /// Key of sandboxed runs with the default limits and no preinstalled dependencies, the ones the pool pre-starts.
+    pub fn default_for(runtime: &dyn LanguageRuntime, config: &Config) -> Self {
+        Self {
+            language: runtime.name().to_string(),
+            image: runtime.image().to_string(),
+            profile: ExecutionProfile::default(),
+            limits: ResourceLimits::resolve(&RequestedLimits::default(), config),
+            code_node_id: None,
+        }
+    }
+
+    /// Key of a run of `code_node_id`. Sandboxed containers only run code of the node that first used them, trusted
+    /// ones are shared by every node.
+    pub fn bound_to(self, code_node_id: i32) -> Self {
+        let code_node_id = match self.profile {
+            ExecutionProfile::Trusted => None,
+            _ => Some(code_node_id),
+        };
+        Self { code_node_id, ..self }
+    }
+}
+
+/// A started container idling until code is run in it with `exec`.
+pub struct WarmContainer {
+    pub id: String,
+    runs: u32,
+}
+
+/// Pre-started runtime containers, runs use one of them through `exec` instead of creating and starting their own.
+///
+/// Each language of `warm_pool_sizes` keeps up to that many idle containers, pre-started with the default settings
+/// and refilled as they are taken. Runs with other settings get a container started on demand, which is kept for
+/// later runs like any other. Sandboxed containers are kept for the node that first ran code in them, and every
+/// container is reset between runs. A container is replaced after `warm_pool_max_runs` runs, or right away when a
+/// run leaves it dirty.
+pub struct WarmPool {
+    config: Arc<Config>,
+    worker_id: uuid::Uuid,
+    idle: Mutex<HashMap<PoolKey, Vec<WarmContainer>>>,
+    filling: AtomicBool,
+}
+
+impl WarmPool {
+    pub fn new(config: Arc<Config>, worker_id: uuid::Uuid) -> Self {
+        Self {
+            config,
+            worker_id,
+            idle: Mutex::new(HashMap::new()),
+            filling: AtomicBool::new(false),
+        }
+    }
+
+    pub fn enabled(config: &Config, language: &str) -> bool {
+        config.warm_pool_sizes.get(language).is_some_and(|size| *size > 0)
+    }
+
+    fn idle_count(&self, language: &str) -> usize {
+        let idle = self.idle.lock().expect("warm pool lock poisoned");
+        idle.iter()
+            .filter(|(key, _)| key.language == language)
+            .map(|(_, containers)| containers.len())
+            .sum()
+    }
+
+    /// Takes an idle container of `key`, or a fresh one with the same settings, or starts one when there is none, then
+    /// refills the pool in the background.
+    pub async fn acquire(self: &Arc<Self>, docker: &Docker, key: &PoolKey) -> Result<WarmContainer, SeraphError> {
+        let fresh = PoolKey {
+            code_node_id: None,
+            ..key.clone()
+        };
+
+        let container = loop {
+            let idle = {
+                let mut idle = self.idle.lock().expect("warm pool lock poisoned");
+                match idle.get_mut(key).and_then(Vec::pop) {
+                    Some(container) => Some(container),
+                    None => idle.get_mut(&fresh).and_then(Vec::pop),
+                }
+            };
+
+            let Some(container) = idle else {
+                break self.start(docker, key).await;
+            };
+
+            // Gone when the task that last used it was cancelled, or when Docker restarted.
+            if is_running(docker, &container.id).await {
+                break Ok(container);
+            }
+            remove_container(docker, &container.id).await;
+        };
+
+        let pool = self.clone();
+        let docker = docker.clone();
+        tokio::spawn(async move { pool.fill(&docker).await });
+
+        container
+    }
+
+    /// Hands a container back after a run. It is removed instead when `dirty`, worn out, or its language has enough idle ones.
+    pub async fn release(&self, docker: &Docker, key: &PoolKey, mut container: WarmContainer, dirty: bool) {
+        container.runs += 1;
+
+        let config = &self.config;
+        let size = config.warm_pool_sizes.get(&key.language).copied().unwrap_or(0);
+        let container = match dirty || container.runs >= config.warm_pool_max_runs || self.idle_count(&key.language) >= size {
+            true => Some(container),
+            false => {
+                let mut idle = self.idle.lock().expect("warm pool lock poisoned");
+                idle.entry(key.clone()).or_default().push(container);
+                None
+            }
+        };
+
+        if let Some(container) = container {
+            tracing::debug!("Recycling warm container {} after {} runs", container.id, container.runs);
+            remove_container(docker, &container.id).await;
+        }
+    }
+
+    /// Starts default containers until every pooled language has its size of idle ones.
+    pub async fn fill(&self, docker: &Docker) {
+        // A fill already running covers the containers this one would start.
+        if self.filling.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        for (language, size) in &self.config.warm_pool_sizes {
+            let Some(runtime) = self.config.runtimes.get(language) else {
+                continue;
+            };
+            let key = PoolKey::default_for(runtime.as_ref(), &self.config);
+
+            while self.idle_count(language) < *size {
+                match self.start(docker, &key).await {
+                    Ok(container) => {
+                        let mut idle = self.idle.lock().expect("warm pool lock poisoned");
+                        idle.entry(key.clone()).or_default().push(container);
+                    }
+                    Err(err) => {
+                        tracing::error!("Failed to start a warm {} container: {}", language, err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.filling.store(false, Ordering::Release);
+    }
+
+    async fn start(&self, docker: &Docker, key: &PoolKey) -> Result<WarmContainer, SeraphError> {
+        // Idles until code is run in it, with the same settings as the container of a single run.
+        let mut body = ContainerCreateBody {
+            working_dir: Some(sandbox::WORK_DIR.to_string()),
+            image: Some(key.image.clone()),
+            cmd: Some(vec!["sleep".to_string(), "infinity".to_string()]),
+            labels: Some([(POOL_LABEL.to_string(), self.worker_id.to_string())].into()),
+            host_config: Some(key.limits.host_config()),
+            ..Default::default()
+        };
+        sandbox::apply(&key.profile, &key.limits, &self.config, &mut body);
+
+        let container = docker.create_container(Some(CreateContainerOptions::default()), body).await?;
+        if let Err(err) = docker.start_container(&container.id, Some(StartContainerOptions::default())).await {
+            remove_container(docker, &container.id).await;
+            return Err(err.into());
+        }
+
+        tracing::debug!("Started warm {} container {}", key.language, container.id);
+        Ok(WarmContainer { id: container.id, runs: 0 })
+    }
+}
+
+async fn is_running(docker: &Docker, container_id: &str) -> bool {
+    docker
+        .inspect_container(container_id, None::<InspectContainerOptions>)
+        .await
+        .ok()
+        .and_then(|inspect| inspect.state)
+        .and_then(|state| state.running)
+        .unwrap_or(false)
+}
+
+/// Removes the warm containers left behind by stopped processes, busy or not.
+///
+/// Containers of other workers are kept while their worker holds a lease. An idle process holds none, its containers
+/// are removed too and `WarmPool::acquire` starts new ones in their place.
+pub async fn sweep(docker: &Docker, db: &DatabaseConnection, worker_id: uuid::Uuid, lease: Duration) -> Result<(), SeraphError> {
+    let options = ListContainersOptions {
+        all: true,
+        filters: Some(HashMap::from([("label".to_string(), vec![POOL_LABEL.to_string()])])),
+        ..Default::default()
+    };
+
+    let mut alive: HashMap<uuid::Uuid, bool> = HashMap::new();
+    for container in docker.list_containers(Some(options)).await? {
+        let Some(id) = container.id else {
+            continue;
+        };
+
+        // A label that is not a worker ID cannot belong to a live worker.
+        let owner = container
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(POOL_LABEL))
+            .and_then(|owner| uuid::Uuid::parse_str(owner).ok());
+
+        let owner_alive = match owner {
+            Some(owner) if owner == worker_id => true,
+            Some(owner) => match alive.get(&owner) {
+                Some(owner_alive) => *owner_alive,
+                None => {
+                    let owner_alive = queue::holds_lease(db, owner, lease).await?;
+                    alive.insert(owner, owner_alive);
+                    owner_alive
+                }
+            },
+            None => false,
+        };
+
+        if !owner_alive {
+            remove_container(docker, &id).await;
+        }
+    }
+
+    Ok(())
+}
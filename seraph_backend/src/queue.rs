@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sea_orm::prelude::TimeDateTimeWithTimeZone;
+use sea_orm::sea_query::{Expr, IntoCondition, LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityName, EntityTrait, FromQueryResult,
+    IntoActiveModel, JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set, TransactionTrait,
+};
+
+use crate::code_node_versions::{Column as CodeNodeVersionColumn, Entity as CodeNodeVersion};
+use crate::code_nodes::{Column as CodeNodeColumn, Entity as CodeNode, Model as CodeNodeModel};
+use crate::code_result::{ActiveModel as CodeResultActiveModel, Column as CodeResultColumn, Entity as CodeResult, Model, StringList};
+use crate::enums::ResultStatus;
+use crate::worker::CodeNodeTask;
+
+/// Persists a task as a pending row of `code_results`, which doubles as the job queue.
+pub async fn enqueue<C: ConnectionTrait>(db: &C, task: &CodeNodeTask) -> Result<Model, DbErr> {
+    CodeResultActiveModel {
+        task_id: Set(task.id),
+        code_node_id: Set(task.node_id),
+        version: Set(task.version),
+        status: Set(ResultStatus::Pending),
+        args: Set(StringList(task.args.clone())),
+        dependencies: Set(StringList(task.dependencies.clone())),
+        timeout_seconds: Set(task.timeout_seconds),
+        workflow_run_id: Set(task.workflow_run_id),
+        step: Set(task.step.clone()),
+        map_index: Set(task.map_index),
+        attempt: Set(1),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+/// Claims the oldest pending task for `worker_id` and marks it as running, skipping tasks written in a `saturated` language
+/// and retried tasks still backing off.
+///
+/// Rows locked by another worker are skipped, so several processes can poll the same table. The returned node is the
+/// revision the task was enqueued with, which may be older than the current one.
+pub async fn claim(db: &DatabaseConnection, worker_id: uuid::Uuid, saturated: &[String]) -> Result<Option<(Model, CodeNodeModel)>, DbErr> {
+    let txn = db.begin().await?;
+
+    let mut select = CodeResult::find()
+        .find_also_related(CodeNode)
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Pending))
+        .filter(
+            CodeResultColumn::AvailableAt
+                .is_null()
+                .or(CodeResultColumn::AvailableAt.lte(TimeDateTimeWithTimeZone::now_utc())),
+        )
+        // Saturation is checked on the revision the task runs, which is missing only for rows older than versioning.
+        .join(
+            JoinType::LeftJoin,
+            CodeResult::belongs_to(CodeNodeVersion)
+                .from(CodeResultColumn::CodeNodeId)
+                .to(CodeNodeVersionColumn::CodeNodeId)
+                .on_condition(|left, right| {
+                    Expr::col((left, CodeResultColumn::Version))
+                        .equals((right, CodeNodeVersionColumn::Version))
+                        .into_condition()
+                })
+                .into(),
+        )
+        .filter(
+            Condition::any()
+                .add(CodeNodeVersionColumn::Language.is_not_in(saturated.iter().cloned()))
+                .add(
+                    CodeNodeVersionColumn::Id
+                        .is_null()
+                        .and(CodeNodeColumn::Language.is_not_in(saturated.iter().cloned())),
+                ),
+        )
+        .order_by_asc(CodeResultColumn::Id)
+        .limit(1);
+
+    // Only lock the queue row, the code node itself stays editable while its task is claimed.
+    QueryTrait::query(&mut select).lock_with_tables_behavior(LockType::Update, [CodeResult.table_ref()], LockBehavior::SkipLocked);
+
+    let pending = match select.one(&txn).await? {
+        Some((pending, Some(node))) => Some((pending, node)),
+        _ => None,
+    };
+
+    let Some(pending) = pending else {
+        txn.commit().await?;
+        return Ok(None);
+    };
+
+    let now = TimeDateTimeWithTimeZone::now_utc();
+    let (pending, node) = pending;
+    let mut claimed = pending.into_active_model();
+    claimed.status = Set(ResultStatus::Running);
+    claimed.worker_id = Set(Some(worker_id));
+    claimed.started_at = Set(Some(now));
+    claimed.heartbeat_at = Set(Some(now));
+    let claimed = claimed.update(&txn).await?;
+
+    let node = match claimed.version == node.version {
+        true => node,
+        false => match CodeNodeVersion::find()
+            .filter(CodeNodeVersionColumn::CodeNodeId.eq(node.id))
+            .filter(CodeNodeVersionColumn::Version.eq(claimed.version))
+            .one(&txn)
+            .await?
+        {
+            Some(version) => version.apply_to(node),
+            None => {
+                tracing::warn!("Version {} of code node {} not found, running the current one", claimed.version, node.id);
+                node
+            }
+        },
+    };
+
+    txn.commit().await?;
+    Ok(Some((claimed, node)))
+}
+
+#[derive(Debug, FromQueryResult)]
+struct StatusCount {
+    status: ResultStatus,
+    language: String,
+    count: i64,
+}
+
+/// Number of queued and running tasks, in total and per language.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct QueueDepth {
+    pub pending: i64,
+    pub running: i64,
+    pub pending_by_language: HashMap<String, i64>,
+    pub running_by_language: HashMap<String, i64>,
+}
+
+pub async fn depth<C: ConnectionTrait>(db: &C) -> Result<QueueDepth, DbErr> {
+    let counts = CodeResult::find()
+        .select_only()
+        .column(CodeResultColumn::Status)
+        .column(CodeNodeColumn::Language)
+        .column_as(CodeResultColumn::Id.count(), "count")
+        .inner_join(CodeNode)
+        .filter(CodeResultColumn::Status.is_in([ResultStatus::Pending, ResultStatus::Running]))
+        .group_by(CodeResultColumn::Status)
+        .group_by(CodeNodeColumn::Language)
+        .into_model::<StatusCount>()
+        .all(db)
+        .await?;
+
+    let mut depth = QueueDepth::default();
+    for count in counts {
+        match count.status {
+            ResultStatus::Pending => {
+                depth.pending += count.count;
+                depth.pending_by_language.insert(count.language, count.count);
+            }
+            _ => {
+                depth.running += count.count;
+                depth.running_by_language.insert(count.language, count.count);
+            }
+        }
+    }
+
+    Ok(depth)
+}
+
+/// Refreshes the lease on a running task so other processes do not treat it as orphaned.
+///
+/// Returns `false` once the task is no longer running for `worker_id`, e.g. after it was cancelled.
+pub async fn heartbeat(db: &DatabaseConnection, result_id: i32, worker_id: uuid::Uuid) -> Result<bool, DbErr> {
+    let result = CodeResult::update_many()
+        .col_expr(CodeResultColumn::HeartbeatAt, Expr::value(TimeDateTimeWithTimeZone::now_utc()))
+        .filter(CodeResultColumn::Id.eq(result_id))
+        .filter(CodeResultColumn::WorkerId.eq(worker_id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Records the container running a task, returns `false` if the task was cancelled in the meantime.
+pub async fn attach_container(db: &DatabaseConnection, result_id: i32, container_id: &str) -> Result<bool, DbErr> {
+    let result = CodeResult::update_many()
+        .col_expr(CodeResultColumn::ContainerId, Expr::value(container_id))
+        .filter(CodeResultColumn::Id.eq(result_id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Marks a task as cancelled if it is still in the `from` state.
+///
+/// Cancelling a pending task removes it from the queue, since workers only claim pending rows.
+pub async fn cancel(db: &DatabaseConnection, result_id: i32, from: ResultStatus) -> Result<bool, DbErr> {
+    let result = CodeResult::update_many()
+        .col_expr(
+            CodeResultColumn::Status,
+            CodeResultColumn::Status.save_as(Expr::val(ResultStatus::Cancelled)),
+        )
+        .col_expr(CodeResultColumn::FinishedAt, Expr::value(TimeDateTimeWithTimeZone::now_utc()))
+        .filter(CodeResultColumn::Id.eq(result_id))
+        .filter(CodeResultColumn::Status.eq(from))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Puts a task claimed by `worker_id` back in the queue, for when the worker cannot run it yet. It can be claimed
+/// again after `delay`, without using up an attempt.
+pub async fn release(db: &DatabaseConnection, result_id: i32, worker_id: uuid::Uuid, delay: Duration) -> Result<bool, DbErr> {
+    let result = CodeResult::update_many()
+        .col_expr(
+            CodeResultColumn::Status,
+            CodeResultColumn::Status.save_as(Expr::val(ResultStatus::Pending)),
+        )
+        .col_expr(CodeResultColumn::WorkerId, Expr::value(Option::<uuid::Uuid>::None))
+        .col_expr(CodeResultColumn::StartedAt, Expr::value(Option::<TimeDateTimeWithTimeZone>::None))
+        .col_expr(CodeResultColumn::HeartbeatAt, Expr::value(Option::<TimeDateTimeWithTimeZone>::None))
+        .col_expr(CodeResultColumn::AvailableAt, Expr::value(TimeDateTimeWithTimeZone::now_utc() + delay))
+        .filter(CodeResultColumn::Id.eq(result_id))
+        .filter(CodeResultColumn::WorkerId.eq(worker_id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Whether `worker_id` runs a task whose lease has not expired, the only sign that the process of a worker is alive.
+pub async fn holds_lease(db: &DatabaseConnection, worker_id: uuid::Uuid, lease: Duration) -> Result<bool, DbErr> {
+    let running = CodeResult::find()
+        .filter(CodeResultColumn::WorkerId.eq(worker_id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .filter(CodeResultColumn::HeartbeatAt.gte(TimeDateTimeWithTimeZone::now_utc() - lease))
+        .count(db)
+        .await?;
+    Ok(running > 0)
+}
+
+/// Puts running tasks whose lease expired back in the queue, returning how many were recovered.
+pub async fn recover_orphans(db: &DatabaseConnection, lease: Duration) -> Result<u64, DbErr> {
+    let expired_before = TimeDateTimeWithTimeZone::now_utc() - lease;
+
+    let result = CodeResult::update_many()
+        .col_expr(
+            CodeResultColumn::Status,
+            CodeResultColumn::Status.save_as(Expr::val(ResultStatus::Pending)),
+        )
+        .col_expr(CodeResultColumn::WorkerId, Expr::value(Option::<uuid::Uuid>::None))
+        .col_expr(CodeResultColumn::StartedAt, Expr::value(Option::<TimeDateTimeWithTimeZone>::None))
+        .col_expr(CodeResultColumn::HeartbeatAt, Expr::value(Option::<TimeDateTimeWithTimeZone>::None))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .filter(
+            CodeResultColumn::HeartbeatAt
+                .lt(expired_before)
+                .or(CodeResultColumn::HeartbeatAt.is_null()),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
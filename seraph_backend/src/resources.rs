@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use bollard::models::HostConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::code_nodes::Model as CodeNode;
+use crate::config::Config;
+
+const MEGABYTE: i64 = 1024 * 1024;
+const CPU_PERIOD: i64 = 100_000;
+
+/// Docker rejects CPU quotas below 1ms, which is 10 thousandths of a core with `CPU_PERIOD`.
+const MIN_CPU_MILLIS: i64 = 10;
+
+/// Container resource limits of a run, resolved from the node settings and capped by the server ceilings.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct ResourceLimits {
+    pub memory_mb: i64,
+    /// CPU time in thousandths of a core, `1000` is one full core.
+    pub cpu_millis: i64,
+    pub pids_limit: i64,
+    pub tmpfs_mb: i64,
+}
+
+/// Limits requested for a code node, unset values fall back to the server defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RequestedLimits {
+    #[serde(default)]
+    pub memory_limit_mb: Option<i64>,
+    #[serde(default)]
+    pub cpu_millis: Option<i64>,
+    #[serde(default)]
+    pub pids_limit: Option<i64>,
+    #[serde(default)]
+    pub tmpfs_size_mb: Option<i64>,
+}
+
+impl RequestedLimits {
+    pub fn of(node: &CodeNode) -> Self {
+        Self {
+            memory_limit_mb: node.memory_limit_mb,
+            cpu_millis: node.cpu_millis,
+            pids_limit: node.pids_limit,
+            tmpfs_size_mb: node.tmpfs_size_mb,
+        }
+    }
+
+    /// Checks the requested limits against the server ceilings.
+    pub fn validate(&self, config: &Config) -> Result<(), String> {
+        let checks = [
+            ("memory_limit_mb", self.memory_limit_mb, 1, config.max_memory_mb),
+            ("cpu_millis", self.cpu_millis, MIN_CPU_MILLIS, config.max_cpu_millis),
+            ("pids_limit", self.pids_limit, 1, config.max_pids_limit),
+            ("tmpfs_size_mb", self.tmpfs_size_mb, 1, config.max_tmpfs_mb),
+        ];
+
+        for (name, value, min, max) in checks {
+            if let Some(value) = value
+                && (value < min || value > max)
+            {
+                return Err(format!("{} must be between {} and {}", name, min, max));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ResourceLimits {
+    pub fn resolve(requested: &RequestedLimits, config: &Config) -> Self {
+        Self {
+            memory_mb: requested.memory_limit_mb.unwrap_or(config.default_memory_mb).min(config.max_memory_mb),
+            // Nodes saved before the minimum was enforced may still hold a lower value.
+            cpu_millis: requested
+                .cpu_millis
+                .unwrap_or(config.default_cpu_millis)
+                .min(config.max_cpu_millis)
+                .max(MIN_CPU_MILLIS),
+            pids_limit: requested.pids_limit.unwrap_or(config.default_pids_limit).min(config.max_pids_limit),
+            tmpfs_mb: requested.tmpfs_size_mb.unwrap_or(config.default_tmpfs_mb).min(config.max_tmpfs_mb),
+        }
+    }
+
+    pub fn host_config(&self) -> HostConfig {
+        let memory = self.memory_mb * MEGABYTE;
+
+        HostConfig {
+            memory: Some(memory),
+            // Same value as `memory` disables swap, so exceeding the limit triggers the OOM killer.
+            memory_swap: Some(memory),
+            cpu_period: Some(CPU_PERIOD),
+            cpu_quota: Some(self.cpu_millis * CPU_PERIOD / 1000),
+            pids_limit: Some(self.pids_limit),
+            tmpfs: Some(HashMap::from([("/tmp".to_string(), format!("rw,size={}m", self.tmpfs_mb))])),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+
+use crate::enums::{ErrorReason, ResultStatus};
+
+/// Upper bound of `RetryPolicy::max_attempts`.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Ways a task can fail that a retry may fix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    Timeout,
+    OutOfMemory,
+    NonZeroExit,
+    /// Seraph itself failed to run the task, e.g. Docker was unavailable.
+    InternalError,
+}
+
+impl FailureClass {
+    /// Class of a failed attempt, `None` for successes and failures a retry cannot fix, such as an output type mismatch.
+    pub fn of(status: &ResultStatus, reason: Option<&ErrorReason>) -> Option<Self> {
+        match (status, reason) {
+            (ResultStatus::Timeout, _) => Some(FailureClass::Timeout),
+            (ResultStatus::Error, Some(ErrorReason::OutOfMemory)) => Some(FailureClass::OutOfMemory),
+            (ResultStatus::Error, Some(ErrorReason::NonZeroExit)) => Some(FailureClass::NonZeroExit),
+            (ResultStatus::Error, Some(ErrorReason::InternalError)) => Some(FailureClass::InternalError),
+            _ => None,
+        }
+    }
+}
+
+/// How a code node retries failed runs, persisted as JSONB. The default runs a task once.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, FromJsonQueryResult)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled for every attempt after it.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Shortens each delay by a random amount of up to half, so tasks failing together do not retry together.
+    pub jitter: bool,
+    pub retry_on: Vec<FailureClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            jitter: true,
+            retry_on: vec![FailureClass::Timeout, FailureClass::NonZeroExit, FailureClass::InternalError],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 || self.max_attempts > MAX_ATTEMPTS {
+            return Err(format!("max_attempts must be between 1 and {}", MAX_ATTEMPTS));
+        }
+        if self.initial_backoff_ms > self.max_backoff_ms {
+            return Err("initial_backoff_ms must not exceed max_backoff_ms".to_string());
+        }
+        Ok(())
+    }
+
+    /// Delay before the attempt following `attempt`, `None` when a failure of `class` is not retried.
+    pub fn backoff(&self, attempt: i32, class: Option<FailureClass>) -> Option<Duration> {
+        let class = class?;
+        if attempt < 1 || attempt as u32 >= self.max_attempts || !self.retry_on.contains(&class) {
+            return None;
+        }
+
+        let delay = self
+            .initial_backoff_ms
+            .saturating_mul(1 << (attempt - 1).min(32))
+            .min(self.max_backoff_ms);
+        let delay = match self.jitter {
+            true => delay - fastrand::u64(0..=delay / 2),
+            false => delay,
+        };
+
+        Some(Duration::from_millis(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32, initial_backoff_ms: u64, max_backoff_ms: u64, jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff_ms,
+            max_backoff_ms,
+            jitter,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_for_every_attempt() {
+        let policy = policy(5, 1000, 60_000, false);
+
+        let delays: Vec<_> = (1..=4).map(|attempt| policy.backoff(attempt, Some(FailureClass::Timeout))).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000].map(|ms| Some(Duration::from_millis(ms))));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = policy(100, 1000, 5000, false);
+
+        assert_eq!(policy.backoff(4, Some(FailureClass::Timeout)), Some(Duration::from_millis(5000)));
+        assert_eq!(policy.backoff(64, Some(FailureClass::Timeout)), Some(Duration::from_millis(5000)));
+    }
+
+    #[test]
+    fn backoff_jitter_shortens_by_up_to_half() {
+        let policy = policy(5, 1000, 60_000, true);
+
+        for _ in 0..200 {
+            let delay = policy.backoff(3, Some(FailureClass::Timeout)).unwrap();
+            assert!(
+                delay >= Duration::from_millis(2000) && delay <= Duration::from_millis(4000),
+                "{:?}",
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_stops_after_the_last_attempt() {
+        assert_eq!(policy(1, 1000, 60_000, false).backoff(1, Some(FailureClass::Timeout)), None);
+
+        let policy = policy(3, 1000, 60_000, false);
+        assert!(policy.backoff(2, Some(FailureClass::Timeout)).is_some());
+        assert_eq!(policy.backoff(3, Some(FailureClass::Timeout)), None);
+        assert_eq!(policy.backoff(0, Some(FailureClass::Timeout)), None);
+    }
+
+    #[test]
+    fn backoff_only_retries_the_listed_failures() {
+        let policy = RetryPolicy {
+            retry_on: vec![FailureClass::Timeout],
+            ..policy(3, 1000, 60_000, false)
+        };
+
+        assert!(policy.backoff(1, Some(FailureClass::Timeout)).is_some());
+        assert_eq!(policy.backoff(1, Some(FailureClass::OutOfMemory)), None);
+        assert_eq!(policy.backoff(1, None), None);
+    }
+
+    #[test]
+    fn default_policy_runs_once() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.validate().is_ok());
+        assert_eq!(policy.backoff(1, Some(FailureClass::Timeout)), None);
+    }
+}
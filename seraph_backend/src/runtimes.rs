@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::prelude::Json;
+use serde::Deserialize;
+
+use crate::code_nodes::Model as CodeNode;
+use crate::dependencies;
+
+/// Where derived images preinstall dependencies, outside of the working directory.
+const PREINSTALLED_DIR: &str = "/opt/seraph";
+
+/// A language a code node can be written in.
+///
+/// Every runtime image ships a harness that takes `module_name function_name [json_args...]`, imports the module
+/// from the working directory, calls the function and writes its JSON encoded return value to `SERAPH_RESULT_PATH`.
+pub trait LanguageRuntime: Send + Sync {
+    /// Identifier stored in `code_nodes.language`.
+    fn name(&self) -> &str;
+
+    /// Docker image the code runs in.
+    fn image(&self) -> &str;
+
+    /// Name of the file the node code is written to inside the working directory.
+    fn source_file(&self, module_name: &str) -> String;
+
+    /// Arguments asking the harness to install `dependencies` before it runs the function.
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String>;
+
+    /// Full container command calling the function of `node` with `args`.
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String>;
+
+    /// Dockerfile instructions preinstalling `dependencies` in an image derived from `image`, see `image_cache`.
+    ///
+    /// Runs in such an image get no dependencies of their own. `None` when the runtime can only install them on every run.
+    fn image_setup(&self, _dependencies: &[String]) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Decodes what the harness wrote to the result file.
+    fn decode_output(&self, raw: &str) -> Result<Json, String> {
+        serde_json::from_str(raw).map_err(|err| format!("Return value is not valid JSON: {}", err))
+    }
+}
+
+#[derive(Default)]
+pub struct Command {
+    command: Vec<String>,
+}
+
+impl Command {
+    pub fn new() -> Self {
+        Self { command: vec![] }
+    }
+
+    pub fn add(&mut self, arg: &str) -> &mut Self {
+        self.command.push(arg.to_string());
+        self
+    }
+
+    pub fn extend(&mut self, args: &[String]) -> &mut Self {
+        self.command.extend_from_slice(args);
+        self
+    }
+
+    pub fn to_vec(&self) -> Vec<String> {
+        self.command.clone()
+    }
+}
+
+fn with_flag(flag: &str, dependencies: &[String]) -> Vec<String> {
+    dependencies.iter().flat_map(|dep| [flag.to_string(), dep.clone()]).collect()
+}
+
+/// Command of a harness taking `[options...] module_name function_name [json_args...]`.
+fn harness_command(entrypoint: &[&str], options: &[String], node: &CodeNode, args: &[String]) -> Vec<String> {
+    let mut command = Command::new();
+    entrypoint.iter().for_each(|part| {
+        command.add(part);
+    });
+
+    command.extend(options).add(&node.name).add(&node.function_name).extend(args).to_vec()
+}
+
+pub struct PythonRuntime;
+
+impl LanguageRuntime for PythonRuntime {
+    fn name(&self) -> &str {
+        "python"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_python:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.py", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        // `uv` installs the dependencies in a throwaway environment, so they go before the script.
+        Command::new()
+            .add("uv")
+            .add("run")
+            .add("-q")
+            .extend(&self.dependency_args(dependencies))
+            .add("/seraph/main.py")
+            .add(&node.name)
+            .add(&node.function_name)
+            .extend(args)
+            .to_vec()
+    }
+
+    fn image_setup(&self, dependencies: &[String]) -> Option<Vec<String>> {
+        // `uv run` picks the active virtual environment up when the node is not a project of its own.
+        Some(vec![
+            format!(
+                "RUN uv venv {venv} && uv pip install --no-cache --python {venv} {}",
+                dependencies::shell_words(dependencies),
+                venv = PREINSTALLED_DIR
+            ),
+            format!("ENV VIRTUAL_ENV={venv} PATH={venv}/bin:$PATH", venv = PREINSTALLED_DIR),
+        ])
+    }
+}
+
+pub struct JavaScriptRuntime;
+
+impl LanguageRuntime for JavaScriptRuntime {
+    fn name(&self) -> &str {
+        "javascript"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_node:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.js", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        // The harness installs the dependencies with npm before importing the module.
+        harness_command(&["node", "/seraph/main.mjs"], &self.dependency_args(dependencies), node, args)
+    }
+
+    fn image_setup(&self, dependencies: &[String]) -> Option<Vec<String>> {
+        // ES modules ignore `NODE_PATH`, but imports resolve up to `/node_modules` from the working directory.
+        Some(vec![format!(
+            "RUN npm install --prefix / --no-audit --no-fund --loglevel=error {}",
+            dependencies::shell_words(dependencies)
+        )])
+    }
+}
+
+/// Shell functions, the return value is what the function prints to stdout.
+pub struct BashRuntime;
+
+impl LanguageRuntime for BashRuntime {
+    fn name(&self) -> &str {
+        "bash"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_bash:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.sh", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        // Printed text has no type of its own, the harness encodes it as the declared output type.
+        let mut options = vec!["--output-type".to_string(), node.output_type.to_string()];
+        options.extend(self.dependency_args(dependencies));
+
+        harness_command(&["bash", "/seraph/main.sh"], &options, node, args)
+    }
+}
+
+pub struct RubyRuntime;
+
+impl LanguageRuntime for RubyRuntime {
+    fn name(&self) -> &str {
+        "ruby"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_ruby:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.rb", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        harness_command(&["ruby", "/seraph/main.rb"], &self.dependency_args(dependencies), node, args)
+    }
+}
+
+/// Go files of `package main`, compiled with `go run` together with a generated entrypoint.
+pub struct GoRuntime;
+
+impl LanguageRuntime for GoRuntime {
+    fn name(&self) -> &str {
+        "go"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_go:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.go", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        harness_command(&["bash", "/seraph/main.sh"], &self.dependency_args(dependencies), node, args)
+    }
+}
+
+/// Rust modules, compiled with `rust-script` together with a generated entrypoint.
+pub struct RustRuntime;
+
+impl LanguageRuntime for RustRuntime {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn image(&self) -> &str {
+        "seraph_rust:latest"
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.rs", module_name)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        with_flag("--with", dependencies)
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        // `Vec<u8>` serializes as an array of numbers, the harness needs the output type to encode bytes as base64.
+        let mut options = vec!["--output-type".to_string(), node.output_type.to_string()];
+        options.extend(self.dependency_args(dependencies));
+
+        harness_command(&["bash", "/seraph/main.sh"], &options, node, args)
+    }
+}
+
+/// A runtime declared in the runtimes config file rather than compiled in.
+///
+/// `command` is a template where `{dependencies}` and `{args}` expand to several arguments, and `{module}` and
+/// `{function}` are replaced inside any argument. `dependency_args` is expanded once per dependency, with
+/// `{dependency}` replaced by its name. `image_setup` lines are Dockerfile instructions where `{dependencies}` is
+/// replaced by the shell quoted dependencies, leave it empty when the image cannot preinstall them.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfiguredRuntime {
+    pub name: String,
+    pub image: String,
+    pub extension: String,
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub dependency_args: Vec<String>,
+    #[serde(default)]
+    pub image_setup: Vec<String>,
+}
+
+impl LanguageRuntime for ConfiguredRuntime {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn image(&self) -> &str {
+        &self.image
+    }
+
+    fn source_file(&self, module_name: &str) -> String {
+        format!("{}.{}", module_name, self.extension)
+    }
+
+    fn dependency_args(&self, dependencies: &[String]) -> Vec<String> {
+        dependencies
+            .iter()
+            .flat_map(|dep| self.dependency_args.iter().map(move |arg| arg.replace("{dependency}", dep)))
+            .collect()
+    }
+
+    fn build_command(&self, node: &CodeNode, args: &[String], dependencies: &[String]) -> Vec<String> {
+        let mut command = Command::new();
+
+        for part in &self.command {
+            match part.as_str() {
+                "{dependencies}" => command.extend(&self.dependency_args(dependencies)),
+                "{args}" => command.extend(args),
+                _ => command.add(&part.replace("{module}", &node.name).replace("{function}", &node.function_name)),
+            };
+        }
+
+        command.to_vec()
+    }
+
+    fn image_setup(&self, dependencies: &[String]) -> Option<Vec<String>> {
+        if self.image_setup.is_empty() {
+            return None;
+        }
+
+        let dependencies = dependencies::shell_words(dependencies);
+        Some(
+            self.image_setup
+                .iter()
+                .map(|line| line.replace("{dependencies}", &dependencies))
+                .collect(),
+        )
+    }
+}
+
+/// Runtimes available to code nodes, keyed by the name stored in `code_nodes.language`.
+#[derive(Clone)]
+pub struct RuntimeRegistry {
+    runtimes: HashMap<String, Arc<dyn LanguageRuntime>>,
+}
+
+impl RuntimeRegistry {
+    /// The runtimes compiled into seraph.
+    pub fn builtin() -> Self {
+        let mut registry = Self { runtimes: HashMap::new() };
+        registry.register(Arc::new(PythonRuntime));
+        registry.register(Arc::new(JavaScriptRuntime));
+        registry.register(Arc::new(BashRuntime));
+        registry.register(Arc::new(RubyRuntime));
+        registry.register(Arc::new(GoRuntime));
+        registry.register(Arc::new(RustRuntime));
+        registry
+    }
+
+    /// The built-in runtimes plus the ones declared in the JSON file at `path`, which may override built-ins.
+    pub fn load(path: Option<&str>) -> Self {
+        let mut registry = Self::builtin();
+
+        if let Some(path) = path {
+            let content = std::fs::read_to_string(path).expect("Failed to read runtimes config");
+            let runtimes: Vec<ConfiguredRuntime> = serde_json::from_str(&content).expect("Invalid runtimes config");
+
+            for runtime in runtimes {
+                tracing::info!("Registering runtime '{}' from {}", runtime.name, path);
+                registry.register(Arc::new(runtime));
+            }
+        }
+
+        registry
+    }
+
+    pub fn register(&mut self, runtime: Arc<dyn LanguageRuntime>) {
+        self.runtimes.insert(runtime.name().to_string(), runtime);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn LanguageRuntime>> {
+        self.runtimes.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.runtimes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl std::fmt::Debug for RuntimeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuntimeRegistry").field("runtimes", &self.names()).finish()
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use bollard::models::{ContainerCreateBody, Mount, MountTypeEnum, MountVolumeOptions, MountVolumeOptionsDriverConfig};
+
+use crate::config::Config;
+use crate::enums::ExecutionProfile;
+use crate::resources::ResourceLimits;
+
+/// Directory the node code is uploaded to and run from.
+pub const WORK_DIR: &str = "/app/";
+
+/// File the runtime harness writes the function return value to, inside `WORK_DIR` so it is writable in every profile.
+pub const RESULT_FILE: &str = "/app/.seraph_result";
+
+/// File the runtime harness lists the installed package versions in, as a JSON array of specifiers.
+pub const DEPENDENCIES_FILE: &str = "/app/.seraph_dependencies";
+
+/// Applies the isolation settings of `profile` to a container definition whose host config is already set.
+///
+/// A sandboxed container has no network (or only the configured allowlist network), a read-only root filesystem,
+/// no capabilities and runs as an unprivileged user. The code directory is a size-limited tmpfs volume, rather than
+/// a plain tmpfs mount, because Docker only accepts uploads into volumes of a container with a read-only root.
+pub fn apply(profile: &ExecutionProfile, limits: &ResourceLimits, config: &Config, container: &mut ContainerCreateBody) {
+    if *profile == ExecutionProfile::Trusted {
+        return;
+    }
+
+    let host_config = container.host_config.get_or_insert_with(Default::default);
+
+    host_config.network_mode = Some(config.sandbox_network.clone().unwrap_or_else(|| "none".to_string()));
+    host_config.readonly_rootfs = Some(true);
+    host_config.cap_drop = Some(vec!["ALL".to_string()]);
+
+    let mut security_opt = vec!["no-new-privileges:true".to_string()];
+    if let Some(seccomp_profile) = &config.sandbox_seccomp_profile {
+        security_opt.push(format!("seccomp={}", seccomp_profile));
+    }
+    host_config.security_opt = Some(security_opt);
+
+    host_config.mounts = Some(vec![Mount {
+        target: Some(WORK_DIR.to_string()),
+        typ: Some(MountTypeEnum::VOLUME),
+        volume_options: Some(MountVolumeOptions {
+            no_copy: Some(true),
+            driver_config: Some(MountVolumeOptionsDriverConfig {
+                name: Some("local".to_string()),
+                options: Some(HashMap::from([
+                    ("type".to_string(), "tmpfs".to_string()),
+                    ("device".to_string(), "tmpfs".to_string()),
+                    ("o".to_string(), format!("size={}m,mode=1777", limits.tmpfs_mb)),
+                ])),
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }]);
+
+    container.user = Some(config.sandbox_user.clone());
+    container.network_disabled = Some(config.sandbox_network.is_none());
+
+    // Only /tmp and the code directory are writable, point tool caches there.
+    container
+        .env
+        .get_or_insert_with(Vec::new)
+        .extend(["HOME=/tmp".to_string(), "UV_CACHE_DIR=/tmp/.uv-cache".to_string()]);
+}
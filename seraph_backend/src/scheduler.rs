@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use sea_orm::prelude::TimeDateTimeWithTimeZone;
+use sea_orm::sea_query::{LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tokio::sync::Notify;
+
+use crate::code_nodes::Entity as CodeNode;
+use crate::code_result::{Column as CodeResultColumn, Entity as CodeResult};
+use crate::config::Config;
+use crate::enums::ResultStatus;
+use crate::queue;
+use crate::schedules::{Column as ScheduleColumn, Entity as Schedule, Model as ScheduleModel};
+use crate::worker::CodeNodeTask;
+
+/// Largest `max_catch_up` of a schedule.
+pub const MAX_CATCH_UP: i32 = 100;
+
+/// A cron expression evaluated in a time zone.
+pub struct Cron {
+    schedule: cron::Schedule,
+    timezone: Tz,
+}
+
+impl Cron {
+    /// Parses an expression of 6 or 7 fields, starting with seconds, or of 5 fields like a crontab entry.
+    pub fn parse(expression: &str, timezone: &str) -> Result<Self, String> {
+        let expression = match expression.split_whitespace().count() {
+            5 => format!("0 {}", expression),
+            _ => expression.to_string(),
+        };
+        let schedule = cron::Schedule::from_str(&expression).map_err(|err| format!("Invalid cron expression: {}", err))?;
+        let timezone = Tz::from_str(timezone).map_err(|_| format!("Unknown time zone: '{}'", timezone))?;
+
+        Ok(Self { schedule, timezone })
+    }
+
+    /// Occurrences strictly after `time`, in order.
+    pub fn after(&self, time: TimeDateTimeWithTimeZone) -> impl Iterator<Item = TimeDateTimeWithTimeZone> + '_ {
+        let time = DateTime::<Utc>::from_timestamp(time.unix_timestamp(), 0).unwrap_or_default();
+        self.schedule
+            .after(&time.with_timezone(&self.timezone))
+            .filter_map(|occurrence| TimeDateTimeWithTimeZone::from_unix_timestamp(occurrence.timestamp()).ok())
+    }
+
+    /// First occurrence strictly after `time`, unset when the expression has none left.
+    pub fn next_after(&self, time: TimeDateTimeWithTimeZone) -> Option<TimeDateTimeWithTimeZone> {
+        self.after(time).next()
+    }
+}
+
+/// Enqueues the runs of due schedules, checking for them every `scheduler_poll_interval_ms`.
+///
+/// Due schedules are locked while their runs are enqueued, so several processes can share the same database.
+/// `notify` wakes the worker up once runs were enqueued.
+pub async fn scheduler(db: Arc<DatabaseConnection>, config: Config, notify: Arc<Notify>) {
+    let poll_interval = Duration::from_millis(config.scheduler_poll_interval_ms);
+
+    loop {
+        match run_due(&db).await {
+            Ok(0) => {}
+            Ok(started) => {
+                tracing::info!("Enqueued {} scheduled runs", started);
+                notify.notify_one();
+            }
+            Err(err) => tracing::error!("Failed to run due schedules: {}", err),
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Enqueues a run of every due schedule, returning how many were started.
+async fn run_due(db: &DatabaseConnection) -> Result<usize, DbErr> {
+    let now = TimeDateTimeWithTimeZone::now_utc();
+    let txn = db.begin().await?;
+
+    // Schedules locked by another process are handled by it.
+    let due = Schedule::find()
+        .filter(ScheduleColumn::Enabled.eq(true))
+        .filter(ScheduleColumn::NextRunAt.lte(now))
+        .order_by_asc(ScheduleColumn::NextRunAt)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(&txn)
+        .await?;
+
+    let mut started = 0;
+    for schedule in due {
+        if run(&txn, schedule, now).await? {
+            started += 1;
+        }
+    }
+
+    txn.commit().await?;
+    Ok(started)
+}
+
+/// Enqueues the oldest due run of `schedule` and moves it to the following one, returns whether a run was enqueued.
+///
+/// A schedule has at most one run pending or running, so scheduled runs need no queue depth check.
+async fn run(txn: &DatabaseTransaction, schedule: ScheduleModel, now: TimeDateTimeWithTimeZone) -> Result<bool, DbErr> {
+    // Runs coming due while the last one is still going are made up for once it is done, within `max_catch_up`.
+    if let Some(task_id) = schedule.last_task_id {
+        let running = CodeResult::find()
+            .filter(CodeResultColumn::TaskId.eq(task_id))
+            .filter(CodeResultColumn::Status.is_in([ResultStatus::Pending, ResultStatus::Running]))
+            .count(txn)
+            .await?;
+        if running > 0 {
+            return Ok(false);
+        }
+    }
+
+    // Expressions are checked when a schedule is saved, so this only fails when they were edited in the database.
+    let cron = match Cron::parse(&schedule.cron, &schedule.timezone) {
+        Ok(cron) => cron,
+        Err(err) => {
+            tracing::error!("Disabling schedule {}: {}", schedule.id, err);
+            let mut disabled = schedule.into_active_model();
+            disabled.enabled = Set(false);
+            disabled.next_run_at = Set(None);
+            disabled.update(txn).await?;
+            return Ok(false);
+        }
+    };
+
+    let Some(next_run_at) = schedule.next_run_at else {
+        return Ok(false);
+    };
+
+    // Every occurrence since the schedule came due, only the last `max_catch_up` missed ones and the latest are kept.
+    let keep = schedule.max_catch_up.max(0) as usize + 1;
+    let mut due = VecDeque::with_capacity(keep);
+    let mut skipped = 0;
+    for occurrence in cron
+        .after(next_run_at - Duration::from_secs(1))
+        .take_while(|occurrence| *occurrence <= now)
+    {
+        if due.len() == keep {
+            due.pop_front();
+            skipped += 1;
+        }
+        due.push_back(occurrence);
+    }
+    if skipped > 0 {
+        tracing::warn!("Schedule {} skipped {} missed runs", schedule.id, skipped);
+    }
+
+    let scheduled_for = due.pop_front();
+    let following = due.front().copied().or_else(|| cron.next_after(now));
+
+    let mut updated = schedule.clone().into_active_model();
+    updated.next_run_at = Set(following);
+
+    let Some(scheduled_for) = scheduled_for else {
+        updated.update(txn).await?;
+        return Ok(false);
+    };
+
+    let Some(node) = CodeNode::find_by_id(schedule.code_node_id).one(txn).await? else {
+        return Ok(false);
+    };
+    let version = schedule.version.unwrap_or(node.version);
+
+    let task =
+        CodeNodeTask::new(node.id, version, schedule.args.0.clone(), schedule.dependencies.0.clone()).with_timeout(schedule.timeout_seconds);
+    queue::enqueue(txn, &task).await?;
+
+    tracing::info!("Schedule {} enqueued task {} for {}", schedule.id, task.id, scheduled_for);
+
+    updated.last_run_at = Set(Some(scheduled_for));
+    updated.last_task_id = Set(Some(task.id));
+    updated.update(txn).await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: i64) -> TimeDateTimeWithTimeZone {
+        TimeDateTimeWithTimeZone::from_unix_timestamp(timestamp).unwrap()
+    }
+
+    // 2026-01-15 00:00:00 UTC.
+    const WINTER: i64 = 1768435200;
+    // 2026-07-15 00:00:00 UTC.
+    const SUMMER: i64 = 1784073600;
+
+    #[test]
+    fn five_fields_run_on_the_minute() {
+        let cron = Cron::parse("* * * * *", "UTC").unwrap();
+
+        assert_eq!(cron.next_after(at(WINTER)), Some(at(WINTER + 60)));
+        assert_eq!(cron.next_after(at(WINTER + 30)), Some(at(WINTER + 60)));
+    }
+
+    #[test]
+    fn six_fields_start_with_seconds() {
+        let cron = Cron::parse("15 * * * * *", "UTC").unwrap();
+
+        let next: Vec<_> = cron.after(at(WINTER)).take(2).collect();
+        assert_eq!(next, [at(WINTER + 15), at(WINTER + 75)]);
+    }
+
+    #[test]
+    fn seven_fields_end_with_years() {
+        assert_eq!(Cron::parse("0 0 0 1 1 * 2025", "UTC").unwrap().next_after(at(WINTER)), None);
+        assert!(Cron::parse("0 0 0 1 1 * 2027", "UTC").unwrap().next_after(at(WINTER)).is_some());
+    }
+
+    #[test]
+    fn occurrences_follow_the_time_zone() {
+        let cron = Cron::parse("30 2 * * *", "Europe/Paris").unwrap();
+
+        // 02:30 in Paris is 01:30 UTC in winter and 00:30 UTC in summer.
+        assert_eq!(cron.next_after(at(WINTER)), Some(at(WINTER + 5400)));
+        assert_eq!(cron.next_after(at(SUMMER)), Some(at(SUMMER + 1800)));
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        for expression in ["", "* * * *", "61 * * * *", "* * * * * * * *", "every day"] {
+            let err = Cron::parse(expression, "UTC").err();
+            assert!(
+                err.is_some_and(|err| err.contains("Invalid cron expression")),
+                "accepted {:?}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_time_zones() {
+        for timezone in ["", "Mars/Olympus", "utc+2"] {
+            let err = Cron::parse("* * * * *", timezone).err();
+            assert!(err.is_some_and(|err| err.contains("Unknown time zone")), "accepted {:?}", timezone);
+        }
+    }
+}
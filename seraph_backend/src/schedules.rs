@@ -0,0 +1,63 @@
+use crate::code_result::StringList;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Runs a code node on a cron schedule, see `scheduler`.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "schedules")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub name: String,
+    pub code_node_id: i32,
+    /// Version of the node to run, the current one when each run is enqueued if unset.
+    pub version: Option<i32>,
+
+    /// Cron expression, the seconds field is optional.
+    pub cron: String,
+    /// IANA time zone the expression is evaluated in, e.g. `Europe/Paris`.
+    pub timezone: String,
+
+    /// JSON encoded arguments passed to every run.
+    #[sea_orm(column_type = "JsonBinary")]
+    pub args: StringList,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub dependencies: StringList,
+    pub timeout_seconds: Option<i32>,
+
+    pub enabled: bool,
+    /// Runs missed while seraph was down or the previous run was still going that are made up for, oldest first.
+    /// Older missed runs are skipped.
+    pub max_catch_up: i32,
+
+    /// Next time the schedule is due, unset while it is disabled.
+    pub next_run_at: Option<TimeDateTimeWithTimeZone>,
+    /// Time the last run was scheduled for, and its task. No run starts while that task is pending or running.
+    pub last_run_at: Option<TimeDateTimeWithTimeZone>,
+    pub last_task_id: Option<uuid::Uuid>,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+    pub updated_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-schedules-code_node_id",
+        belongs_to = "super::code_nodes::Entity",
+        from = "Column::CodeNodeId",
+        to = "super::code_nodes::Column::Id",
+        on_delete = "Cascade"
+    )]
+    CodeNode,
+}
+
+impl Related<super::code_nodes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeNode.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
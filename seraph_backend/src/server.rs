@@ -1,12 +1,46 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::code_nodes::{ActiveModel as CodeNodeActiveModel, Entity as CodeNode};
+use crate::code_node_files::{
+    self, ActiveModel as CodeNodeFileActiveModel, Column as CodeNodeFileColumn, Entity as CodeNodeFile, FileInfo, Model as CodeNodeFileModel,
+};
+use crate::code_node_version_files;
+use crate::code_node_versions::{
+    ActiveModel as CodeNodeVersionActiveModel, Column as CodeNodeVersionColumn, Entity as CodeNodeVersion, Model as CodeNodeVersionModel,
+};
+use crate::code_nodes::{ActiveModel as CodeNodeActiveModel, Column as CodeNodeColumn, Entity as CodeNode, Model as CodeNodeModel};
+use crate::code_result::{Column as CodeResultColumn, Entity as CodeResult, Model as CodeResultModel, StringList};
+use crate::code_result_attempts::{Column as CodeResultAttemptColumn, Entity as CodeResultAttempt};
 use crate::config;
-use crate::enums::{CodeLanguage, OutputType};
+use crate::dependencies;
+use crate::enums::{ExecutionProfile, OutputType, ResultStatus};
+use crate::error::SeraphError;
+use crate::image_cache::{self, Entry};
+use crate::queue;
+use crate::resources::RequestedLimits;
+use crate::retry::RetryPolicy;
+use crate::scheduler::{Cron, MAX_CATCH_UP};
+use crate::schedules::{ActiveModel as ScheduleActiveModel, Column as ScheduleColumn, Entity as Schedule, Model as ScheduleModel};
 use crate::worker::CodeNodeTask;
-use actix_web::{App, HttpResponse, HttpServer, Responder, delete, get, middleware, post, web};
-use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, EntityTrait, IntoActiveModel, Set};
-use tokio::sync::mpsc;
+use crate::workflow_edges::{
+    ActiveModel as WorkflowEdgeActiveModel, Column as WorkflowEdgeColumn, Entity as WorkflowEdge, Model as WorkflowEdgeModel,
+};
+use crate::workflow_engine;
+use crate::workflow_runs::{Column as WorkflowRunColumn, Entity as WorkflowRun, Model as WorkflowRunModel};
+use crate::workflows::{
+    ActiveModel as WorkflowActiveModel, Column as WorkflowColumn, Entity as Workflow, Model as WorkflowModel, Step, StepKind, Steps,
+};
+use actix_web::{App, HttpResponse, HttpServer, Responder, ResponseError, delete, get, middleware, patch, post, put, web};
+use bollard::Docker;
+use sea_orm::prelude::TimeDateTimeWithTimeZone;
+use sea_orm::sea_query::extension::postgres::PgExpr;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, Database, DatabaseConnection, EntityTrait, FromQueryResult, IntoActiveModel, Order,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tokio::sync::Notify;
 use tokio::task;
 
 #[get("/")]
@@ -14,14 +48,14 @@ async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello, world!")
 }
 
-#[get("/code-node/{id}/")]
-async fn get_code_node(id: web::Path<i32>, data: web::Data<AppState>) -> impl Responder {
-    let post = CodeNode::find_by_id(id.into_inner()).one(&*data.db).await.unwrap();
+async fn find_code_node<C: ConnectionTrait>(db: &C, id: i32) -> Result<CodeNodeModel, SeraphError> {
+    CodeNode::find_by_id(id).one(db).await?.ok_or(SeraphError::NotFound("Code node"))
+}
 
-    match post {
-        Some(p) => HttpResponse::Ok().json(p),
-        None => HttpResponse::NotFound().body("Post not found"),
-    }
+#[get("/code-node/{id}/")]
+async fn get_code_node(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(node))
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -31,88 +65,1305 @@ struct CreateCodeNode {
     code: String,
     output_name: String,
     output_type: OutputType,
-    language: CodeLanguage,
+    language: String,
+
+    #[serde(default)]
+    timeout_seconds: Option<i32>,
+
+    #[serde(flatten)]
+    limits: RequestedLimits,
+
+    #[serde(default)]
+    execution_profile: ExecutionProfile,
+
+    #[serde(default)]
+    dependencies: Vec<String>,
+
+    #[serde(default)]
+    retry_policy: RetryPolicy,
+}
+
+/// Checks the settings of a created or updated code node.
+fn validate_code_node(
+    language: &str,
+    timeout_seconds: Option<i32>,
+    limits: &RequestedLimits,
+    dependencies: &[String],
+    retry_policy: &RetryPolicy,
+    config: &config::Config,
+) -> Result<(), String> {
+    if config.runtimes.get(language).is_none() {
+        return Err(format!("Unsupported language: '{}'", language));
+    }
+
+    dependencies::validate(dependencies)?;
+
+    if timeout_seconds.is_some_and(|timeout| timeout <= 0) {
+        return Err("timeout_seconds must be positive".to_string());
+    }
+
+    retry_policy.validate()?;
+    limits.validate(config)
 }
 
 #[post("/code-node/")]
-async fn create_code_node(data: web::Data<AppState>, node: web::Json<CreateCodeNode>) -> impl Responder {
+async fn create_code_node(data: web::Data<AppState>, node: web::Json<CreateCodeNode>) -> Result<HttpResponse, SeraphError> {
     let node = node.into_inner();
 
+    validate_code_node(
+        &node.language,
+        node.timeout_seconds,
+        &node.limits,
+        &node.dependencies,
+        &node.retry_policy,
+        &data.config,
+    )
+    .map_err(SeraphError::InvalidInput)?;
+
     let post = CodeNodeActiveModel {
         name: Set(node.name),
         function_name: Set(node.function_name),
-        code: Set(serde_json::to_string(&node.code).unwrap()),
+        code: Set(encode_code(&node.code)),
         output_name: Set(node.output_name),
         output_type: Set(node.output_type),
         language: Set(node.language),
+        timeout_seconds: Set(node.timeout_seconds),
+        memory_limit_mb: Set(node.limits.memory_limit_mb),
+        cpu_millis: Set(node.limits.cpu_millis),
+        pids_limit: Set(node.limits.pids_limit),
+        tmpfs_size_mb: Set(node.limits.tmpfs_size_mb),
+        execution_profile: Set(node.execution_profile),
+        dependencies: Set(StringList(node.dependencies)),
+        retry_policy: Set(node.retry_policy),
+        version: Set(1),
         ..Default::default()
     };
 
-    match post.insert(&*data.db).await {
-        Ok(created_node) => HttpResponse::Created().json(created_node),
-        Err(err) => {
-            tracing::error!("Failed to create code node: {}", err);
-            HttpResponse::InternalServerError().body("Failed to create code node")
+    let txn = data.db.begin().await?;
+    let created_node = post.insert(&txn).await?;
+    CodeNodeVersionActiveModel::of(&created_node).insert(&txn).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Created().json(created_node))
+}
+
+/// Code is stored JSON encoded, see `code_nodes::alter_code`.
+fn encode_code(code: &str) -> String {
+    serde_json::Value::String(code.to_string()).to_string()
+}
+
+/// Deserializes a field that distinguishes being absent, `None`, from being `null`, `Some(None)`.
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// Changes to a code node, absent fields are left untouched and `null` clears an optional setting.
+#[derive(serde::Deserialize)]
+struct UpdateCodeNode {
+    name: Option<String>,
+    function_name: Option<String>,
+    code: Option<String>,
+    output_name: Option<String>,
+    output_type: Option<OutputType>,
+    language: Option<String>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    timeout_seconds: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    memory_limit_mb: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    cpu_millis: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pids_limit: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    tmpfs_size_mb: Option<Option<i64>>,
+
+    execution_profile: Option<ExecutionProfile>,
+
+    dependencies: Option<Vec<String>>,
+
+    retry_policy: Option<RetryPolicy>,
+}
+
+/// A full replacement, settings left out of the body go back to their defaults.
+impl From<CreateCodeNode> for UpdateCodeNode {
+    fn from(node: CreateCodeNode) -> Self {
+        Self {
+            name: Some(node.name),
+            function_name: Some(node.function_name),
+            code: Some(node.code),
+            output_name: Some(node.output_name),
+            output_type: Some(node.output_type),
+            language: Some(node.language),
+            timeout_seconds: Some(node.timeout_seconds),
+            memory_limit_mb: Some(node.limits.memory_limit_mb),
+            cpu_millis: Some(node.limits.cpu_millis),
+            pids_limit: Some(node.limits.pids_limit),
+            tmpfs_size_mb: Some(node.limits.tmpfs_size_mb),
+            execution_profile: Some(node.execution_profile),
+            dependencies: Some(node.dependencies),
+            retry_policy: Some(node.retry_policy),
         }
     }
 }
 
+impl UpdateCodeNode {
+    fn apply(self, node: &CodeNodeModel) -> CodeNodeModel {
+        let node = node.clone();
+
+        CodeNodeModel {
+            name: self.name.unwrap_or(node.name),
+            function_name: self.function_name.unwrap_or(node.function_name),
+            code: self.code.map(|code| encode_code(&code)).unwrap_or(node.code),
+            output_name: self.output_name.unwrap_or(node.output_name),
+            output_type: self.output_type.unwrap_or(node.output_type),
+            language: self.language.unwrap_or(node.language),
+            timeout_seconds: self.timeout_seconds.unwrap_or(node.timeout_seconds),
+            memory_limit_mb: self.memory_limit_mb.unwrap_or(node.memory_limit_mb),
+            cpu_millis: self.cpu_millis.unwrap_or(node.cpu_millis),
+            pids_limit: self.pids_limit.unwrap_or(node.pids_limit),
+            tmpfs_size_mb: self.tmpfs_size_mb.unwrap_or(node.tmpfs_size_mb),
+            execution_profile: self.execution_profile.unwrap_or(node.execution_profile),
+            dependencies: self.dependencies.map(StringList).unwrap_or(node.dependencies),
+            retry_policy: self.retry_policy.unwrap_or(node.retry_policy),
+            ..node
+        }
+    }
+}
+
+/// Applies `update` to a code node, recording a new version when one of its runnable fields changed.
+async fn update_code_node(id: i32, data: &AppState, update: UpdateCodeNode) -> Result<HttpResponse, SeraphError> {
+    let txn = data.db.begin().await?;
+
+    let node = lock_code_node(&txn, id).await?;
+
+    let mut updated = update.apply(&node);
+
+    validate_code_node(
+        &updated.language,
+        updated.timeout_seconds,
+        &RequestedLimits::of(&updated),
+        &updated.dependencies.0,
+        &updated.retry_policy,
+        &data.config,
+    )
+    .map_err(SeraphError::InvalidInput)?;
+
+    if updated.name != node.name || updated.language != node.language {
+        check_main_module_free(&txn, &data.config, &updated).await?;
+    }
+
+    // Limits, timeout, execution profile and retry policy are operational settings and apply to every version.
+    let new_version = CodeNodeVersionActiveModel::of(&updated) != CodeNodeVersionActiveModel::of(&node);
+    if new_version {
+        updated.version = node.version + 1;
+    }
+
+    let updated = updated.into_active_model().reset_all().update(&txn).await?;
+    if new_version {
+        CodeNodeVersionActiveModel::of(&updated).insert(&txn).await?;
+        code_node_version_files::snapshot(&txn, updated.id, updated.version).await?;
+    }
+    txn.commit().await?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[put("/code-node/{id}/")]
+async fn replace_code_node(id: web::Path<i32>, data: web::Data<AppState>, node: web::Json<CreateCodeNode>) -> Result<HttpResponse, SeraphError> {
+    update_code_node(id.into_inner(), &data, node.into_inner().into()).await
+}
+
+#[patch("/code-node/{id}/")]
+async fn patch_code_node(id: web::Path<i32>, data: web::Data<AppState>, update: web::Json<UpdateCodeNode>) -> Result<HttpResponse, SeraphError> {
+    update_code_node(id.into_inner(), &data, update.into_inner()).await
+}
+
+#[get("/code-node/{id}/versions")]
+async fn get_code_node_versions(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let versions = CodeNodeVersion::find()
+        .filter(CodeNodeVersionColumn::CodeNodeId.eq(id.into_inner()))
+        .order_by_desc(CodeNodeVersionColumn::Version)
+        .all(&*data.db)
+        .await?;
+
+    // Every node has at least its first version.
+    if versions.is_empty() {
+        return Err(SeraphError::NotFound("Code node"));
+    }
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+#[get("/code-node/{id}/versions/{version}")]
+async fn get_code_node_version(path: web::Path<(i32, i32)>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let (id, version) = path.into_inner();
+    let version = find_version(&*data.db, id, version).await?;
+
+    Ok(HttpResponse::Ok().json(version))
+}
+
+async fn find_version<C: ConnectionTrait>(db: &C, id: i32, version: i32) -> Result<CodeNodeVersionModel, SeraphError> {
+    CodeNodeVersion::find()
+        .filter(CodeNodeVersionColumn::CodeNodeId.eq(id))
+        .filter(CodeNodeVersionColumn::Version.eq(version))
+        .one(db)
+        .await?
+        .ok_or(SeraphError::NotFound("Version"))
+}
+
 #[delete("/code-node/{id}/")]
-async fn delete_code_node(id: web::Path<i32>, data: web::Data<AppState>) -> impl Responder {
-    let node = match CodeNode::find_by_id(id.into_inner()).one(&*data.db).await {
-        Ok(Some(node)) => node,
-        Ok(None) => return HttpResponse::NotFound().body("Code node not found"),
-        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+async fn delete_code_node(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+    node.into_active_model().delete(&*data.db).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/code-node/{id}/files")]
+async fn get_code_node_files(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+
+    let files = CodeNodeFile::find()
+        .filter(CodeNodeFileColumn::CodeNodeId.eq(node.id))
+        .order_by_asc(CodeNodeFileColumn::Path)
+        .into_partial_model::<FileInfo>()
+        .all(&*data.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(files))
+}
+
+async fn find_file<C: ConnectionTrait>(db: &C, id: i32, path: &str) -> Result<CodeNodeFileModel, SeraphError> {
+    CodeNodeFile::find()
+        .filter(CodeNodeFileColumn::CodeNodeId.eq(id))
+        .filter(CodeNodeFileColumn::Path.eq(path))
+        .one(db)
+        .await?
+        .ok_or(SeraphError::NotFound("File"))
+}
+
+#[get("/code-node/{id}/files/{path:.*}")]
+async fn get_code_node_file(path: web::Path<(i32, String)>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let (id, path) = path.into_inner();
+    let file = find_file(&*data.db, id, &path).await?;
+
+    Ok(HttpResponse::Ok().content_type("application/octet-stream").body(file.content))
+}
+
+/// Creates or replaces a file of the node with the raw request body.
+#[put("/code-node/{id}/files/{path:.*}")]
+async fn put_code_node_file(path: web::Path<(i32, String)>, data: web::Data<AppState>, body: web::Bytes) -> Result<HttpResponse, SeraphError> {
+    let (id, path) = path.into_inner();
+    code_node_files::validate_path(&path).map_err(SeraphError::InvalidInput)?;
+
+    let txn = data.db.begin().await?;
+    let node = lock_code_node(&txn, id).await?;
+
+    // The main module would overwrite the file in the container.
+    if main_module(&data.config, &node).is_some_and(|main_module| main_module == path) {
+        return Err(SeraphError::Conflict(format!("'{}' is the main module of the node", path)));
+    }
+
+    let file = CodeNodeFileActiveModel {
+        code_node_id: Set(node.id),
+        path: Set(path.clone()),
+        size: Set(body.len() as i64),
+        content: Set(body.to_vec()),
+        updated_at: Set(TimeDateTimeWithTimeZone::now_utc()),
+        ..Default::default()
     };
 
-    match node.into_active_model().delete(&*data.db).await {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(err) => {
-            tracing::error!("Failed to delete code node: {}", err);
-            HttpResponse::InternalServerError().body("Failed to delete code node")
-        }
+    CodeNodeFile::insert(file)
+        .on_conflict(
+            OnConflict::columns([CodeNodeFileColumn::CodeNodeId, CodeNodeFileColumn::Path])
+                .update_columns([CodeNodeFileColumn::Content, CodeNodeFileColumn::Size, CodeNodeFileColumn::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+
+    record_files_version(&txn, node).await?;
+    let file = find_file(&txn, id, &path).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Ok().json(FileInfo {
+        path: file.path,
+        size: file.size,
+        updated_at: file.updated_at,
+    }))
+}
+
+#[delete("/code-node/{id}/files/{path:.*}")]
+async fn delete_code_node_file(path: web::Path<(i32, String)>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let (id, path) = path.into_inner();
+
+    let txn = data.db.begin().await?;
+    let node = lock_code_node(&txn, id).await?;
+    let file = find_file(&txn, id, &path).await?;
+    file.into_active_model().delete(&txn).await?;
+
+    record_files_version(&txn, node).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Locks a code node until the end of the transaction, so concurrent changes cannot both claim the next version number.
+async fn lock_code_node<C: ConnectionTrait>(db: &C, id: i32) -> Result<CodeNodeModel, SeraphError> {
+    CodeNode::find_by_id(id)
+        .lock_exclusive()
+        .one(db)
+        .await?
+        .ok_or(SeraphError::NotFound("Code node"))
+}
+
+/// Path of the main module of `node` in the working directory, `None` when its language is unknown.
+fn main_module(config: &config::Config, node: &CodeNodeModel) -> Option<String> {
+    config.runtimes.get(&node.language).map(|runtime| runtime.source_file(&node.name))
+}
+
+/// Checks that no file of `node` has the path its main module would be written to.
+async fn check_main_module_free<C: ConnectionTrait>(db: &C, config: &config::Config, node: &CodeNodeModel) -> Result<(), SeraphError> {
+    let Some(main_module) = main_module(config, node) else {
+        return Ok(());
+    };
+
+    let taken = CodeNodeFile::find()
+        .filter(CodeNodeFileColumn::CodeNodeId.eq(node.id))
+        .filter(CodeNodeFileColumn::Path.eq(&main_module))
+        .count(db)
+        .await?;
+
+    match taken {
+        0 => Ok(()),
+        _ => Err(SeraphError::Conflict(format!(
+            "The main module of the node would overwrite its file '{}'",
+            main_module
+        ))),
     }
 }
 
+/// Records a new version of a node whose files changed, with a copy of its current files.
+async fn record_files_version<C: ConnectionTrait>(db: &C, node: CodeNodeModel) -> Result<(), SeraphError> {
+    let version = node.version + 1;
+    let mut node = node.into_active_model();
+    node.version = Set(version);
+    let node = node.update(db).await?;
+
+    CodeNodeVersionActiveModel::of(&node).insert(db).await?;
+    code_node_version_files::snapshot(db, node.id, node.version).await?;
+    Ok(())
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 struct RunCodeNode {
     #[serde(default)]
     args: Vec<String>,
 
+    /// Installed on top of the node's own dependencies, replacing those of the same package.
     #[serde(default)]
     dependencies: Vec<String>,
+
+    /// Overrides the node's timeout for this run only.
+    #[serde(default)]
+    timeout_seconds: Option<i32>,
+
+    /// Runs an older version of the node instead of the current one.
+    #[serde(default)]
+    version: Option<i32>,
+}
+
+#[derive(serde::Deserialize)]
+struct RunQuery {
+    /// How long to wait for the result before answering with the task ID, e.g. `30s` or `500ms`.
+    wait: Option<String>,
+}
+
+/// Interval at which a waiting run request checks whether its task finished.
+const RUN_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parses a wait of `<n>ms`, `<n>s` or `<n>m`, a bare number is in seconds.
+fn parse_wait(wait: &str, max_seconds: u64) -> Result<Duration, String> {
+    let wait = wait.trim();
+    let (amount, unit) = match wait.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => wait.split_at(index),
+        None => (wait, "s"),
+    };
+
+    let amount: u64 = amount.parse().map_err(|_| format!("Invalid wait: '{}'", wait))?;
+    let wait = match unit {
+        "ms" => Duration::from_millis(amount),
+        "s" => Duration::from_secs(amount),
+        "m" => Duration::from_secs(amount.saturating_mul(60)),
+        _ => return Err(format!("Invalid wait: '{}', use ms, s or m", wait)),
+    };
+
+    match wait <= Duration::from_secs(max_seconds) {
+        true => Ok(wait),
+        false => Err(format!("wait must not exceed {} seconds", max_seconds)),
+    }
+}
+
+/// Waits for a task to finish, returns `None` when it did not within `wait`.
+///
+/// The task may run in another process, so its result is polled rather than signalled.
+async fn wait_for_result<C: ConnectionTrait>(db: &C, task_id: uuid::Uuid, wait: Duration) -> Result<Option<CodeResultModel>, SeraphError> {
+    let deadline = tokio::time::Instant::now() + wait;
+
+    loop {
+        let result = find_task(db, task_id).await?;
+        if result.status.is_finished() {
+            return Ok(Some(result));
+        }
+
+        if tokio::time::Instant::now() + RUN_WAIT_POLL_INTERVAL > deadline {
+            return Ok(None);
+        }
+        tokio::time::sleep(RUN_WAIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Applies backpressure instead of letting the queue grow without bounds.
+async fn check_queue_depth<C: ConnectionTrait>(db: &C, config: &config::Config) -> Result<(), SeraphError> {
+    if config.max_queue_depth == 0 {
+        return Ok(());
+    }
+
+    let pending = CodeResult::find()
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Pending))
+        .count(db)
+        .await?;
+
+    match pending >= config.max_queue_depth {
+        true => Err(SeraphError::QueueFull { retry_after: 5 }),
+        false => Ok(()),
+    }
 }
 
 #[post("/code-node/{id}/run")]
 async fn run_code_node(
     id: web::Path<i32>,
     data: web::Data<AppState>,
-    sender: web::Data<mpsc::Sender<CodeNodeTask>>,
+    notify: web::Data<Notify>,
     run_input: web::Json<RunCodeNode>,
-) -> impl Responder {
-    let node = match CodeNode::find_by_id(id.into_inner()).one(&*data.db).await {
-        Ok(Some(node)) => node,
-        Ok(None) => return HttpResponse::NotFound().body("Code node not found"),
-        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
-    };
+    query: web::Query<RunQuery>,
+) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+
+    let wait = query
+        .wait
+        .as_deref()
+        .map(|wait| parse_wait(wait, data.config.max_run_wait_seconds))
+        .transpose()
+        .map_err(SeraphError::InvalidInput)?;
+
+    if run_input.timeout_seconds.is_some_and(|timeout| timeout <= 0) {
+        return Err(SeraphError::InvalidInput("timeout_seconds must be positive".to_string()));
+    }
+
+    dependencies::validate(&run_input.dependencies).map_err(SeraphError::InvalidInput)?;
+
+    let version = run_input.version.unwrap_or(node.version);
+    if version != node.version {
+        find_version(&*data.db, node.id, version).await?;
+    }
+
+    let task =
+        CodeNodeTask::new(node.id, version, run_input.args.clone(), run_input.dependencies.clone()).with_timeout(run_input.timeout_seconds);
 
-    let task = CodeNodeTask::new(node.id, data.db.clone(), run_input.args.clone(), run_input.dependencies.clone());
+    let txn = data.db.begin().await?;
 
-    tracing::info!("Sending task for code node with ID: {}", node.id);
-    if sender.send(task.clone()).await.is_err() {
-        tracing::error!("Failed to send task to worker");
-        return HttpResponse::InternalServerError().body("Failed to send task to worker");
+    if let Err(err) = check_queue_depth(&txn, &data.config).await {
+        tracing::warn!("Rejecting task for code node {}: queue is full", node.id);
+        return Err(err);
     }
 
-    HttpResponse::Accepted().json(serde_json::json!({
+    queue::enqueue(&txn, &task).await?;
+    txn.commit().await?;
+
+    tracing::info!("Enqueued task for code node with ID: {}", node.id);
+    notify.notify_one();
+
+    // Quick runs answer with their return value, the others fall back to the task ID once the wait is over.
+    if let Some(wait) = wait
+        && let Some(result) = wait_for_result(&*data.db, task.id, wait).await?
+    {
+        if result.status != ResultStatus::Success {
+            return Err(SeraphError::RunFailed {
+                task_id: result.task_id,
+                status: result.status,
+                error_reason: result.error_reason,
+                error_message: result.error_message,
+                exit_code: result.exit_code,
+            });
+        }
+
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "task_id": result.task_id,
+            "status": result.status,
+            "return_value": result.return_value,
+        })));
+    }
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
         "message": "Code node execution started",
         "task_id": task.id,
         "node_id": node.id,
-    }))
+        "version": task.version,
+    })))
+}
+
+/// Dependencies to prepare on top of the node's own, like the `dependencies` of a run.
+#[derive(serde::Deserialize)]
+struct PrepareCodeNode {
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Builds the dependency image runs of the node use, so the first of them does not wait for it.
+///
+/// Answers 200 once the image is ready or its build failed, and 202 while it is being built.
+#[post("/code-node/{id}/prepare")]
+async fn prepare_code_node(
+    id: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: Option<web::Json<PrepareCodeNode>>,
+) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+
+    let extras = body.map(|body| body.into_inner().dependencies).unwrap_or_default();
+    dependencies::validate(&extras).map_err(SeraphError::InvalidInput)?;
+    let dependencies = dependencies::merge(&node.dependencies.0, &extras);
+
+    let runtime = data
+        .config
+        .runtimes
+        .get(&node.language)
+        .ok_or_else(|| SeraphError::Internal(format!("No runtime is registered for language '{}'", node.language)))?;
+
+    let docker = Docker::connect_with_defaults()?;
+    let entry = image_cache::lookup(&data.db, &docker, &data.config, runtime.as_ref(), &dependencies, true)
+        .await?
+        .ok_or_else(|| {
+            SeraphError::InvalidInput(format!(
+                "Nothing to prepare: no dependencies, image caching disabled, or runtime '{}' installs them on every run",
+                node.language
+            ))
+        })?;
+
+    match entry {
+        Entry::Ready(entry) | Entry::Failed(entry) => Ok(HttpResponse::Ok().json(entry)),
+        Entry::Building(entry) => Ok(HttpResponse::Accepted().json(entry)),
+        Entry::Claimed(entry, setup) => {
+            let response = HttpResponse::Accepted().json(&entry);
+
+            let db = data.db.clone();
+            let cache_size = data.config.dependency_image_cache_size;
+            task::spawn(async move {
+                if let Err(err) = image_cache::build(&db, &docker, entry, setup, cache_size).await {
+                    tracing::error!("Failed to prepare dependency image: {}", err);
+                }
+            });
+
+            Ok(response)
+        }
+    }
+}
+
+#[get("/queue")]
+async fn get_queue(data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let depth = queue::depth(&*data.db).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "pending": depth.pending,
+        "running": depth.running,
+        "pending_by_language": depth.pending_by_language,
+        "running_by_language": depth.running_by_language,
+        "worker_concurrency": data.config.worker_concurrency,
+        "worker_language_limits": data.config.worker_language_limits,
+        "max_queue_depth": data.config.max_queue_depth,
+    })))
+}
+
+async fn find_task<C: ConnectionTrait>(db: &C, task_id: uuid::Uuid) -> Result<CodeResultModel, SeraphError> {
+    CodeResult::find()
+        .filter(CodeResultColumn::TaskId.eq(task_id))
+        .one(db)
+        .await?
+        .ok_or(SeraphError::NotFound("Task"))
+}
+
+#[get("/task/{task_id}")]
+async fn get_task(task_id: web::Path<uuid::Uuid>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let result = find_task(&*data.db, task_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[get("/task/{task_id}/attempts")]
+async fn get_task_attempts(task_id: web::Path<uuid::Uuid>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let result = find_task(&*data.db, task_id.into_inner()).await?;
+
+    let attempts = CodeResultAttempt::find()
+        .filter(CodeResultAttemptColumn::CodeResultId.eq(result.id))
+        .order_by_asc(CodeResultAttemptColumn::Attempt)
+        .all(&*data.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(attempts))
+}
+
+#[post("/task/{task_id}/cancel")]
+async fn cancel_task(task_id: web::Path<uuid::Uuid>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let task_id = task_id.into_inner();
+    let cancelled = || HttpResponse::Ok().json(serde_json::json!({ "task_id": task_id, "status": ResultStatus::Cancelled }));
+
+    let mut result = find_task(&*data.db, task_id).await?;
+
+    // A pending task is dropped from the queue, unless a worker claimed it in the meantime.
+    if result.status == ResultStatus::Pending {
+        if queue::cancel(&data.db, result.id, ResultStatus::Pending).await? {
+            if let Some(run_id) = result.workflow_run_id {
+                workflow_engine::advance(&data.db, run_id).await?;
+            }
+            return Ok(cancelled());
+        }
+        result = find_task(&*data.db, task_id).await?;
+    }
+
+    if result.status != ResultStatus::Running {
+        return Err(SeraphError::Conflict(format!("Task is already {}", result.status)));
+    }
+
+    if !queue::cancel(&data.db, result.id, ResultStatus::Running).await? {
+        return Err(SeraphError::Conflict("Task finished before it could be cancelled".to_string()));
+    }
+
+    // A cancelled step cancels its workflow run.
+    if let Some(run_id) = result.workflow_run_id {
+        workflow_engine::advance(&data.db, run_id).await?;
+    }
+
+    // Without a container the worker has not started it yet, and will see the cancellation before it does.
+    if let Some(container_id) = result.container_id {
+        tracing::info!("Stopping container {} of cancelled task {}", container_id, task_id);
+        match Docker::connect_with_defaults() {
+            Ok(docker) => crate::worker::remove_container(&docker, &container_id).await,
+            Err(err) => tracing::error!("Failed to connect to Docker to stop task {}: {}", task_id, err),
+        }
+    }
+
+    Ok(cancelled())
+}
+
+const MAX_PAGE_SIZE: u64 = 100;
+
+fn validate_page(page: u64, per_page: u64) -> Result<(), SeraphError> {
+    if page == 0 || per_page == 0 || per_page > MAX_PAGE_SIZE {
+        return Err(SeraphError::InvalidInput(format!(
+            "page must be >= 1 and per_page between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(())
+}
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_per_page() -> u64 {
+    20
+}
+
+#[derive(serde::Deserialize)]
+struct CodeResultsQuery {
+    #[serde(default = "default_page")]
+    page: u64,
+
+    #[serde(default = "default_per_page")]
+    per_page: u64,
+
+    status: Option<ResultStatus>,
+}
+
+#[get("/code-node/{id}/results")]
+async fn get_code_node_results(
+    id: web::Path<i32>,
+    data: web::Data<AppState>,
+    query: web::Query<CodeResultsQuery>,
+) -> Result<HttpResponse, SeraphError> {
+    let node = find_code_node(&*data.db, id.into_inner()).await?;
+    validate_page(query.page, query.per_page)?;
+
+    let mut select = CodeResult::find().filter(CodeResultColumn::CodeNodeId.eq(node.id));
+    if let Some(status) = query.status.clone() {
+        select = select.filter(CodeResultColumn::Status.eq(status));
+    }
+
+    let paginator = select.order_by_desc(CodeResultColumn::Id).paginate(&*data.db, query.per_page);
+    let counts = paginator.num_items_and_pages().await?;
+    let items = paginator.fetch_page(query.page - 1).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "items": items,
+        "page": query.page,
+        "per_page": query.per_page,
+        "total": counts.number_of_items,
+        "pages": counts.number_of_pages,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct CodeNodesQuery {
+    #[serde(default = "default_page")]
+    page: u64,
+
+    #[serde(default = "default_per_page")]
+    per_page: u64,
+
+    /// Field to sort by, prefixed with `-` for descending order.
+    sort: Option<String>,
+
+    language: Option<String>,
+    output_type: Option<OutputType>,
+    /// Case-insensitive substring of the node name.
+    name: Option<String>,
+}
+
+#[derive(FromQueryResult)]
+struct LatestStatus {
+    code_node_id: i32,
+    status: ResultStatus,
+}
+
+#[derive(serde::Serialize)]
+struct CodeNodeSummary {
+    #[serde(flatten)]
+    node: CodeNodeModel,
+    /// Status of the most recent run, `None` if the node never ran.
+    latest_status: Option<ResultStatus>,
+}
+
+#[get("/code-node/")]
+async fn list_code_nodes(data: web::Data<AppState>, query: web::Query<CodeNodesQuery>) -> Result<HttpResponse, SeraphError> {
+    validate_page(query.page, query.per_page)?;
+
+    let sort = query.sort.as_deref().unwrap_or("id");
+    let (field, order) = match sort.strip_prefix('-') {
+        Some(field) => (field, Order::Desc),
+        None => (sort, Order::Asc),
+    };
+    let column = match field {
+        "id" => CodeNodeColumn::Id,
+        "name" => CodeNodeColumn::Name,
+        "language" => CodeNodeColumn::Language,
+        "output_type" => CodeNodeColumn::OutputType,
+        "version" => CodeNodeColumn::Version,
+        _ => return Err(SeraphError::InvalidInput(format!("Unsupported sort field: '{}'", field))),
+    };
+
+    let mut select = CodeNode::find();
+    if let Some(language) = &query.language {
+        select = select.filter(CodeNodeColumn::Language.eq(language));
+    }
+    if let Some(output_type) = query.output_type.clone() {
+        select = select.filter(CodeNodeColumn::OutputType.eq(output_type));
+    }
+    if let Some(name) = &query.name {
+        // Wildcards typed by the caller are matched literally.
+        let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        select = select.filter(Expr::col(CodeNodeColumn::Name).ilike(format!("%{}%", escaped)));
+    }
+
+    // Ties are broken by id so pages stay stable.
+    let paginator = select
+        .order_by(column, order)
+        .order_by_asc(CodeNodeColumn::Id)
+        .paginate(&*data.db, query.per_page);
+
+    let counts = paginator.num_items_and_pages().await?;
+    let nodes = paginator.fetch_page(query.page - 1).await?;
+
+    // One row per node, the one with the highest id.
+    let latest = CodeResult::find()
+        .select_only()
+        .column(CodeResultColumn::CodeNodeId)
+        .column(CodeResultColumn::Status)
+        .distinct_on([CodeResultColumn::CodeNodeId])
+        .filter(CodeResultColumn::CodeNodeId.is_in(nodes.iter().map(|node| node.id)))
+        .order_by_asc(CodeResultColumn::CodeNodeId)
+        .order_by_desc(CodeResultColumn::Id)
+        .into_model::<LatestStatus>()
+        .all(&*data.db)
+        .await?;
+
+    let mut latest: HashMap<i32, ResultStatus> = latest.into_iter().map(|latest| (latest.code_node_id, latest.status)).collect();
+
+    let items: Vec<CodeNodeSummary> = nodes
+        .into_iter()
+        .map(|node| CodeNodeSummary {
+            latest_status: latest.remove(&node.id),
+            node,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "items": items,
+        "page": query.page,
+        "per_page": query.per_page,
+        "total": counts.number_of_items,
+        "pages": counts.number_of_pages,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct SaveWorkflow {
+    name: String,
+    steps: Vec<Step>,
+
+    #[serde(default)]
+    edges: Vec<WorkflowEdgeModel>,
+}
+
+impl SaveWorkflow {
+    /// Checks the graph, and that the code nodes and versions of the steps exist.
+    ///
+    /// Code steps feeding a condition or a map must return a boolean or an array. Values passed on by other control
+    /// steps are only known when the workflow runs.
+    async fn validate<C: ConnectionTrait>(&self, db: &C) -> Result<(), SeraphError> {
+        workflow_engine::validate(&self.steps, &self.edges).map_err(SeraphError::InvalidInput)?;
+
+        let mut output_types = HashMap::new();
+        for step in &self.steps {
+            let Some(code_node_id) = step.code_node_id else {
+                continue;
+            };
+
+            let output_type = match step.version {
+                Some(version) => CodeNodeVersion::find()
+                    .filter(CodeNodeVersionColumn::CodeNodeId.eq(code_node_id))
+                    .filter(CodeNodeVersionColumn::Version.eq(version))
+                    .one(db)
+                    .await?
+                    .map(|version| version.output_type),
+                None => CodeNode::find_by_id(code_node_id).one(db).await?.map(|node| node.output_type),
+            };
+            let Some(output_type) = output_type else {
+                return Err(SeraphError::InvalidInput(format!(
+                    "Code node {} of step '{}' does not exist at the requested version",
+                    code_node_id, step.name
+                )));
+            };
+            output_types.insert(step.name.as_str(), output_type);
+        }
+
+        for edge in self.edges.iter().filter(|edge| edge.argument == 0) {
+            let expected = match self.steps.iter().find(|step| step.name == edge.to_step).map(|step| step.kind) {
+                Some(StepKind::Condition) => OutputType::Boolean,
+                Some(StepKind::Map) => OutputType::Array,
+                _ => continue,
+            };
+            let from = self.steps.iter().find(|step| step.name == edge.from_step);
+            if let Some(from) = from.filter(|from| from.kind == StepKind::Code)
+                && let Some(output_type) = output_types.get(from.name.as_str())
+                && *output_type != expected
+            {
+                return Err(SeraphError::InvalidInput(format!(
+                    "Step '{}' takes a {} as argument 0, but '{}' returns a {}",
+                    edge.to_step, expected, from.name, output_type
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A workflow along with its edges.
+#[derive(serde::Serialize)]
+struct WorkflowDetail {
+    #[serde(flatten)]
+    workflow: WorkflowModel,
+    edges: Vec<WorkflowEdgeModel>,
+}
+
+async fn find_workflow<C: ConnectionTrait>(db: &C, id: i32) -> Result<WorkflowModel, SeraphError> {
+    Workflow::find_by_id(id).one(db).await?.ok_or(SeraphError::NotFound("Workflow"))
+}
+
+async fn find_edges<C: ConnectionTrait>(db: &C, workflow_id: i32) -> Result<Vec<WorkflowEdgeModel>, SeraphError> {
+    Ok(WorkflowEdge::find()
+        .filter(WorkflowEdgeColumn::WorkflowId.eq(workflow_id))
+        .order_by_asc(WorkflowEdgeColumn::Id)
+        .all(db)
+        .await?)
+}
+
+/// Replaces the edges of a workflow.
+async fn save_edges<C: ConnectionTrait>(db: &C, workflow_id: i32, edges: Vec<WorkflowEdgeModel>) -> Result<Vec<WorkflowEdgeModel>, SeraphError> {
+    WorkflowEdge::delete_many()
+        .filter(WorkflowEdgeColumn::WorkflowId.eq(workflow_id))
+        .exec(db)
+        .await?;
+
+    let mut saved = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let edge = WorkflowEdgeActiveModel {
+            workflow_id: Set(workflow_id),
+            from_step: Set(edge.from_step),
+            to_step: Set(edge.to_step),
+            argument: Set(edge.argument),
+            branch: Set(edge.branch),
+            ..Default::default()
+        };
+        saved.push(edge.insert(db).await?);
+    }
+
+    Ok(saved)
+}
+
+#[post("/workflow/")]
+async fn create_workflow(data: web::Data<AppState>, workflow: web::Json<SaveWorkflow>) -> Result<HttpResponse, SeraphError> {
+    let workflow = workflow.into_inner();
+    workflow.validate(&*data.db).await?;
+
+    let txn = data.db.begin().await?;
+    let created = WorkflowActiveModel {
+        name: Set(workflow.name),
+        steps: Set(Steps(workflow.steps)),
+        updated_at: Set(TimeDateTimeWithTimeZone::now_utc()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    let edges = save_edges(&txn, created.id, workflow.edges).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Created().json(WorkflowDetail { workflow: created, edges }))
+}
+
+#[derive(serde::Deserialize)]
+struct WorkflowsQuery {
+    #[serde(default = "default_page")]
+    page: u64,
+
+    #[serde(default = "default_per_page")]
+    per_page: u64,
+}
+
+#[get("/workflow/")]
+async fn list_workflows(data: web::Data<AppState>, query: web::Query<WorkflowsQuery>) -> Result<HttpResponse, SeraphError> {
+    validate_page(query.page, query.per_page)?;
+
+    let paginator = Workflow::find().order_by_asc(WorkflowColumn::Id).paginate(&*data.db, query.per_page);
+    let counts = paginator.num_items_and_pages().await?;
+    let items = paginator.fetch_page(query.page - 1).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "items": items,
+        "page": query.page,
+        "per_page": query.per_page,
+        "total": counts.number_of_items,
+        "pages": counts.number_of_pages,
+    })))
+}
+
+#[get("/workflow/{id}/")]
+async fn get_workflow(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let workflow = find_workflow(&*data.db, id.into_inner()).await?;
+    let edges = find_edges(&*data.db, workflow.id).await?;
+
+    Ok(HttpResponse::Ok().json(WorkflowDetail { workflow, edges }))
+}
+
+/// Replaces the steps and edges of a workflow, runs already started keep the graph they started with.
+#[put("/workflow/{id}/")]
+async fn replace_workflow(
+    id: web::Path<i32>,
+    data: web::Data<AppState>,
+    workflow: web::Json<SaveWorkflow>,
+) -> Result<HttpResponse, SeraphError> {
+    let workflow = workflow.into_inner();
+    workflow.validate(&*data.db).await?;
+
+    let txn = data.db.begin().await?;
+    let existing = Workflow::find_by_id(id.into_inner())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(SeraphError::NotFound("Workflow"))?;
+
+    let mut updated = existing.into_active_model();
+    updated.name = Set(workflow.name);
+    updated.steps = Set(Steps(workflow.steps));
+    updated.updated_at = Set(TimeDateTimeWithTimeZone::now_utc());
+    let updated = updated.update(&txn).await?;
+    let edges = save_edges(&txn, updated.id, workflow.edges).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Ok().json(WorkflowDetail { workflow: updated, edges }))
+}
+
+#[delete("/workflow/{id}/")]
+async fn delete_workflow(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let workflow = find_workflow(&*data.db, id.into_inner()).await?;
+
+    let txn = data.db.begin().await?;
+
+    // Results of its runs are kept, but steps still queued would run on their own once the runs are gone.
+    let runs: Vec<i32> = WorkflowRun::find()
+        .select_only()
+        .column(WorkflowRunColumn::Id)
+        .filter(WorkflowRunColumn::WorkflowId.eq(workflow.id))
+        .into_tuple()
+        .all(&txn)
+        .await?;
+
+    CodeResult::update_many()
+        .col_expr(
+            CodeResultColumn::Status,
+            CodeResultColumn::Status.save_as(Expr::val(ResultStatus::Cancelled)),
+        )
+        .col_expr(CodeResultColumn::FinishedAt, Expr::value(TimeDateTimeWithTimeZone::now_utc()))
+        .filter(CodeResultColumn::WorkflowRunId.is_in(runs))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Pending))
+        .exec(&txn)
+        .await?;
+
+    workflow.into_active_model().delete(&txn).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/workflow/{id}/run")]
+async fn run_workflow(id: web::Path<i32>, data: web::Data<AppState>, notify: web::Data<Notify>) -> Result<HttpResponse, SeraphError> {
+    let workflow = find_workflow(&*data.db, id.into_inner()).await?;
+    let edges = find_edges(&*data.db, workflow.id).await?;
+
+    let txn = data.db.begin().await?;
+
+    if let Err(err) = check_queue_depth(&txn, &data.config).await {
+        tracing::warn!("Rejecting run of workflow {}: queue is full", workflow.id);
+        return Err(err);
+    }
+
+    let run = workflow_engine::start(&txn, &workflow, edges).await?;
+    txn.commit().await?;
+
+    tracing::info!("Started run {} of workflow {}", run.run_id, workflow.id);
+    notify.notify_one();
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Workflow run started",
+        "run_id": run.run_id,
+        "workflow_id": workflow.id,
+    })))
+}
+
+/// A workflow run along with the results of the steps that were enqueued so far.
+#[derive(serde::Serialize)]
+struct WorkflowRunDetail {
+    #[serde(flatten)]
+    run: WorkflowRunModel,
+    results: Vec<CodeResultModel>,
+}
+
+#[get("/workflow-run/{run_id}")]
+async fn get_workflow_run(run_id: web::Path<uuid::Uuid>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let run = WorkflowRun::find()
+        .filter(WorkflowRunColumn::RunId.eq(run_id.into_inner()))
+        .one(&*data.db)
+        .await?
+        .ok_or(SeraphError::NotFound("Workflow run"))?;
+
+    let results = CodeResult::find()
+        .filter(CodeResultColumn::WorkflowRunId.eq(run.id))
+        .order_by_asc(CodeResultColumn::Id)
+        .all(&*data.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(WorkflowRunDetail { run, results }))
+}
+
+#[derive(serde::Deserialize)]
+struct SaveSchedule {
+    name: String,
+    code_node_id: i32,
+    #[serde(default)]
+    version: Option<i32>,
+
+    cron: String,
+    #[serde(default = "default_timezone")]
+    timezone: String,
+
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    #[serde(default)]
+    timeout_seconds: Option<i32>,
+
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default = "default_max_catch_up")]
+    max_catch_up: i32,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_catch_up() -> i32 {
+    1
+}
+
+impl SaveSchedule {
+    /// Checks the settings and that the code node and version exist, returns the parsed expression.
+    async fn validate<C: ConnectionTrait>(&self, db: &C) -> Result<Cron, SeraphError> {
+        let cron = Cron::parse(&self.cron, &self.timezone).map_err(SeraphError::InvalidInput)?;
+
+        let node = CodeNode::find_by_id(self.code_node_id).one(db).await?;
+        let Some(node) = node else {
+            return Err(SeraphError::InvalidInput(format!("Code node {} does not exist", self.code_node_id)));
+        };
+        if let Some(version) = self.version
+            && version != node.version
+        {
+            find_version(db, node.id, version)
+                .await
+                .map_err(|_| SeraphError::InvalidInput(format!("Code node {} has no version {}", node.id, version)))?;
+        }
+
+        dependencies::validate(&self.dependencies).map_err(SeraphError::InvalidInput)?;
+
+        if self.timeout_seconds.is_some_and(|timeout| timeout <= 0) {
+            return Err(SeraphError::InvalidInput("timeout_seconds must be positive".to_string()));
+        }
+        if !(0..=MAX_CATCH_UP).contains(&self.max_catch_up) {
+            return Err(SeraphError::InvalidInput(format!("max_catch_up must be between 0 and {}", MAX_CATCH_UP)));
+        }
+
+        Ok(cron)
+    }
+
+    /// Sets the fields of `schedule`. It restarts from the current time, runs missed so far are not made up for.
+    fn apply(self, cron: &Cron, schedule: &mut ScheduleActiveModel) {
+        let now = TimeDateTimeWithTimeZone::now_utc();
+
+        schedule.name = Set(self.name);
+        schedule.code_node_id = Set(self.code_node_id);
+        schedule.version = Set(self.version);
+        schedule.cron = Set(self.cron);
+        schedule.timezone = Set(self.timezone);
+        schedule.args = Set(StringList(self.args));
+        schedule.dependencies = Set(StringList(self.dependencies));
+        schedule.timeout_seconds = Set(self.timeout_seconds);
+        schedule.enabled = Set(self.enabled);
+        schedule.max_catch_up = Set(self.max_catch_up);
+        schedule.next_run_at = Set(self.enabled.then(|| cron.next_after(now)).flatten());
+        schedule.updated_at = Set(now);
+    }
+}
+
+async fn find_schedule<C: ConnectionTrait>(db: &C, id: i32) -> Result<ScheduleModel, SeraphError> {
+    Schedule::find_by_id(id).one(db).await?.ok_or(SeraphError::NotFound("Schedule"))
+}
+
+#[post("/schedule/")]
+async fn create_schedule(data: web::Data<AppState>, schedule: web::Json<SaveSchedule>) -> Result<HttpResponse, SeraphError> {
+    let schedule = schedule.into_inner();
+    let cron = schedule.validate(&*data.db).await?;
+
+    let mut created: ScheduleActiveModel = Default::default();
+    schedule.apply(&cron, &mut created);
+    let created = created.insert(&*data.db).await?;
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[derive(serde::Deserialize)]
+struct SchedulesQuery {
+    #[serde(default = "default_page")]
+    page: u64,
+
+    #[serde(default = "default_per_page")]
+    per_page: u64,
+
+    code_node_id: Option<i32>,
+}
+
+#[get("/schedule/")]
+async fn list_schedules(data: web::Data<AppState>, query: web::Query<SchedulesQuery>) -> Result<HttpResponse, SeraphError> {
+    validate_page(query.page, query.per_page)?;
+
+    let mut select = Schedule::find().order_by_asc(ScheduleColumn::Id);
+    if let Some(code_node_id) = query.code_node_id {
+        select = select.filter(ScheduleColumn::CodeNodeId.eq(code_node_id));
+    }
+
+    let paginator = select.paginate(&*data.db, query.per_page);
+    let counts = paginator.num_items_and_pages().await?;
+    let items = paginator.fetch_page(query.page - 1).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "items": items,
+        "page": query.page,
+        "per_page": query.per_page,
+        "total": counts.number_of_items,
+        "pages": counts.number_of_pages,
+    })))
+}
+
+#[get("/schedule/{id}/")]
+async fn get_schedule(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let schedule = find_schedule(&*data.db, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+#[put("/schedule/{id}/")]
+async fn replace_schedule(
+    id: web::Path<i32>,
+    data: web::Data<AppState>,
+    schedule: web::Json<SaveSchedule>,
+) -> Result<HttpResponse, SeraphError> {
+    let schedule = schedule.into_inner();
+    let cron = schedule.validate(&*data.db).await?;
+
+    // Locked so the scheduler does not start a run from the settings being replaced.
+    let txn = data.db.begin().await?;
+    let existing = Schedule::find_by_id(id.into_inner())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(SeraphError::NotFound("Schedule"))?;
+
+    let mut updated = existing.into_active_model();
+    schedule.apply(&cron, &mut updated);
+    let updated = updated.update(&txn).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[delete("/schedule/{id}/")]
+async fn delete_schedule(id: web::Path<i32>, data: web::Data<AppState>) -> Result<HttpResponse, SeraphError> {
+    let schedule = find_schedule(&*data.db, id.into_inner()).await?;
+    schedule.into_active_model().delete(&*data.db).await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 struct AppState {
     db: Arc<DatabaseConnection>,
     config: config::Config,
@@ -141,22 +1392,56 @@ pub async fn server() -> std::io::Result<()> {
         config: config.clone(),
     };
 
-    let (sender, receiver) = mpsc::channel(32);
+    let notify = web::Data::new(Notify::new());
     tracing::info!("Starting worker thread");
-    task::spawn(crate::worker::worker(receiver));
+    task::spawn(crate::worker::worker(_conn.clone(), config.clone(), notify.clone().into_inner()));
+    tracing::info!("Starting scheduler");
+    task::spawn(crate::scheduler::scheduler(_conn.clone(), config.clone(), notify.clone().into_inner()));
 
     HttpServer::new(move || {
         App::new()
             .service(hello)
+            .service(list_code_nodes)
             .service(get_code_node)
             .service(run_code_node)
+            .service(prepare_code_node)
             .service(create_code_node)
             .service(delete_code_node)
+            .service(replace_code_node)
+            .service(patch_code_node)
+            .service(get_code_node_versions)
+            .service(get_code_node_version)
+            .service(get_code_node_files)
+            .service(get_code_node_file)
+            .service(put_code_node_file)
+            .service(delete_code_node_file)
+            .service(get_task)
+            .service(get_task_attempts)
+            .service(get_queue)
+            .service(cancel_task)
+            .service(get_code_node_results)
+            .service(list_workflows)
+            .service(create_workflow)
+            .service(get_workflow)
+            .service(replace_workflow)
+            .service(delete_workflow)
+            .service(run_workflow)
+            .service(get_workflow_run)
+            .service(list_schedules)
+            .service(create_schedule)
+            .service(get_schedule)
+            .service(replace_schedule)
+            .service(delete_schedule)
             .app_data(web::Data::new(app_state.clone()))
-            .app_data(web::Data::new(sender.clone()))
+            .app_data(notify.clone())
+            // Malformed requests get the same JSON error body as handler errors.
+            .app_data(web::JsonConfig::default().error_handler(|err, _| SeraphError::InvalidInput(err.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| SeraphError::InvalidInput(err.to_string()).into()))
+            .app_data(web::PayloadConfig::new(app_state.config.max_file_size_bytes))
+            .app_data(web::PathConfig::default().error_handler(|err, _| SeraphError::InvalidInput(err.to_string()).into()))
             .wrap(middleware::Logger::default())
             .wrap(actix_cors::Cors::default().allow_any_origin().allow_any_method().allow_any_header())
-            .default_service(web::route().to(|| async { HttpResponse::NotFound().body("Not Found") }))
+            .default_service(web::route().to(|| async { SeraphError::NotFound("Route").error_response() }))
     })
     .bind((config.server_address, config.server_port))?
     .run()
@@ -170,3 +1455,33 @@ pub fn main() {
         println!("Error: {err}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wait_reads_units() {
+        assert_eq!(parse_wait("500ms", 60), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_wait("30s", 60), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_wait("1m", 60), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_wait(" 45 ", 60), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_wait("0", 60), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_wait_caps_at_the_maximum() {
+        assert_eq!(parse_wait("60s", 60), Ok(Duration::from_secs(60)));
+        assert!(parse_wait("61s", 60).is_err());
+        assert!(parse_wait("60001ms", 60).is_err());
+        assert!(parse_wait("2m", 60).is_err());
+        assert!(parse_wait(&format!("{}m", u64::MAX), 60).is_err());
+    }
+
+    #[test]
+    fn parse_wait_rejects_bad_input() {
+        for wait in ["", "s", "-1s", "1.5s", "10h", "10 s", "ten"] {
+            assert!(parse_wait(wait, 60).is_err(), "accepted {:?}", wait);
+        }
+    }
+}
@@ -1,152 +1,925 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bollard::Docker;
 use bollard::body_try_stream;
-use bollard::models::ContainerCreateBody;
+use bollard::container::LogOutput;
+use bollard::errors::Error as DockerError;
+use bollard::exec::{StartExecOptions, StartExecResults};
+use bollard::models::{ContainerCreateBody, ExecConfig};
 use bollard::query_parameters::CreateContainerOptions;
+use bollard::query_parameters::DownloadFromContainerOptions;
+use bollard::query_parameters::InspectContainerOptions;
+use bollard::query_parameters::KillContainerOptions;
 use bollard::query_parameters::LogsOptions;
 use bollard::query_parameters::StartContainerOptions;
 use bollard::query_parameters::UploadToContainerOptions;
 use bollard::query_parameters::WaitContainerOptions;
 use futures_util::{StreamExt, TryFutureExt};
 use sea_orm::DatabaseConnection;
+use sea_orm::prelude::{Json, TimeDateTimeWithTimeZone};
 use tokio::fs::File;
-use tokio::sync::mpsc;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{Notify, Semaphore};
 use tokio_util::io::ReaderStream;
+use tokio_util::task::AbortOnDropHandle;
 use uuid;
 
-use crate::code_nodes::Entity as CodeNode;
+use crate::code_node_version_files::{Column as CodeNodeVersionFileColumn, Entity as CodeNodeVersionFile, Model as CodeNodeVersionFileModel};
+use crate::code_nodes::Model as CodeNodeModel;
+use crate::code_result::{
+    ActiveModel as CodeResultActiveModel, Column as CodeResultColumn, Entity as CodeResult, Model as CodeResultModel, StringList,
+};
+use crate::code_result_attempts::ActiveModel as CodeResultAttemptActiveModel;
+use crate::config::Config;
+use crate::dependencies;
+use crate::enums::{ErrorReason, ExecutionProfile, OutputType, ResultStatus};
+use crate::error::SeraphError;
+use crate::image_cache::{self, Resolution};
+use crate::pool::{self, PoolKey, WarmContainer, WarmPool};
+use crate::queue;
+use crate::resources::{RequestedLimits, ResourceLimits};
+use crate::retry::{FailureClass, RetryPolicy};
+use crate::runtimes::LanguageRuntime;
+use crate::sandbox;
+use crate::workflow_engine;
 use bollard::query_parameters::RemoveContainerOptions;
-use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, Set, TransactionTrait};
 
 #[derive(Debug, Clone)]
 pub struct CodeNodeTask {
     pub id: uuid::Uuid,
     pub node_id: i32,
+    pub version: i32,
     pub args: Vec<String>,
     pub dependencies: Vec<String>,
-    db: Arc<DatabaseConnection>,
+    pub timeout_seconds: Option<i32>,
+    /// Workflow run and step the task belongs to, along with the element it runs for when the step is a map.
+    pub workflow_run_id: Option<i32>,
+    pub step: Option<String>,
+    pub map_index: Option<i32>,
 }
 
 impl CodeNodeTask {
-    pub fn new(node_id: i32, db: Arc<DatabaseConnection>, args: Vec<String>, dependencies: Vec<String>) -> Self {
+    pub fn new(node_id: i32, version: i32, args: Vec<String>, dependencies: Vec<String>) -> Self {
         Self {
             id: uuid::Uuid::new_v4(),
             node_id,
+            version,
             args,
-            db,
             dependencies,
+            timeout_seconds: None,
+            workflow_run_id: None,
+            step: None,
+            map_index: None,
         }
     }
+
+    pub fn with_timeout(mut self, timeout_seconds: Option<i32>) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn in_workflow(mut self, run_id: i32, step: &str, map_index: Option<i32>) -> Self {
+        self.workflow_run_id = Some(run_id);
+        self.step = Some(step.to_string());
+        self.map_index = map_index;
+        self
+    }
 }
 
-pub async fn worker(mut receiver: mpsc::Receiver<CodeNodeTask>) {
-    while let Some(task) = receiver.recv().await {
-        tracing::info!("Processing code node with ID: {}", task.id);
+/// Runs a pool of at most `worker_concurrency` tasks at a time, claimed from the `code_results` queue.
+///
+/// Languages listed in `worker_language_limits` get their own, smaller, share of the pool. Tasks of a saturated
+/// language stay in the queue so they can be claimed by another process or once a slot frees up.
+/// `notify` is signalled by the server after enqueueing so local tasks are picked up without waiting for the next poll.
+pub async fn worker(db: Arc<DatabaseConnection>, config: Config, notify: Arc<Notify>) {
+    let config = Arc::new(config);
+    let worker_id = uuid::Uuid::new_v4();
+    let poll_interval = Duration::from_millis(config.worker_poll_interval_ms);
+    let lease = Duration::from_secs(config.worker_lease_seconds);
+
+    let slots = Arc::new(Semaphore::new(config.worker_concurrency));
+    let language_slots: HashMap<String, Arc<Semaphore>> = config
+        .worker_language_limits
+        .iter()
+        .map(|(language, limit)| (language.clone(), Arc::new(Semaphore::new(*limit))))
+        .collect();
+    let released = Arc::new(Notify::new());
+
+    let pool = Arc::new(WarmPool::new(config.clone(), worker_id));
+    if config.warm_pool_sizes.values().any(|size| *size > 0) {
+        match Docker::connect_with_defaults() {
+            Ok(docker) => {
+                if let Err(err) = pool::sweep(&docker, &db, worker_id, lease).await {
+                    tracing::error!("Failed to remove stale warm containers: {}", err);
+                }
+                let pool = pool.clone();
+                tokio::spawn(async move { pool.fill(&docker).await });
+            }
+            Err(err) => tracing::error!("Failed to connect to Docker to start warm containers: {}", err),
+        }
+    }
+
+    tracing::info!("Worker {} started with {} slots", worker_id, config.worker_concurrency);
+
+    let mut last_recovery: Option<std::time::Instant> = None;
 
-        let node = match CodeNode::find_by_id(task.node_id).one(&*task.db).await {
-            Ok(Some(node)) => node,
-            _ => {
-                tracing::error!("Code node with ID {} not found", task.id);
+    loop {
+        // Tasks of crashed processes stop heartbeating; put them back in the queue once their lease expires.
+        if last_recovery.is_none_or(|at| at.elapsed() >= lease) {
+            match queue::recover_orphans(&db, lease).await {
+                Ok(0) => {}
+                Ok(recovered) => tracing::warn!("Recovered {} orphaned tasks", recovered),
+                Err(err) => tracing::error!("Failed to recover orphaned tasks: {}", err),
+            }
+            if let Err(err) = workflow_engine::recover(&db).await {
+                tracing::error!("Failed to advance workflow runs: {}", err);
+            }
+            last_recovery = Some(std::time::Instant::now());
+        }
+
+        let slot = slots.clone().acquire_owned().await.expect("worker semaphore closed");
+
+        let saturated: Vec<String> = language_slots
+            .iter()
+            .filter(|(_, slots)| slots.available_permits() == 0)
+            .map(|(language, _)| language.clone())
+            .collect();
+
+        let claimed = queue::claim(&db, worker_id, &saturated).await;
+
+        let (code_result, node) = match claimed {
+            Ok(Some(claimed)) => claimed,
+            Ok(None) => {
+                drop(slot);
+                tokio::select! {
+                    _ = notify.notified() => {},
+                    _ = released.notified() => {},
+                    _ = tokio::time::sleep(poll_interval) => {},
+                }
+                continue;
+            }
+            Err(err) => {
+                tracing::error!("Failed to claim a task: {}", err);
+                drop(slot);
+                tokio::time::sleep(poll_interval).await;
                 continue;
             }
         };
 
-        let code_result = crate::code_result::ActiveModel {
-            code_node_id: Set(node.id),
-            status: Set(crate::enums::ResultStatus::Pending),
-            output: Set(None),
-            task_id: Set(task.id),
-            ..Default::default()
+        // Only this loop acquires language slots, so the availability checked when claiming still holds, unless the
+        // task runs a revision written in a saturated language that was changed since. It then goes back in the queue.
+        let language_slot = match language_slots.get(&node.language).map(|slots| slots.clone().try_acquire_owned()) {
+            Some(Ok(language_slot)) => Some(language_slot),
+            None => None,
+            Some(Err(_)) => {
+                tracing::warn!(
+                    "Language '{}' of task {} is saturated, putting it back",
+                    node.language,
+                    code_result.task_id
+                );
+                if let Err(err) = queue::release(&db, code_result.id, worker_id, Duration::ZERO).await {
+                    tracing::error!("Failed to put task {} back in the queue: {}", code_result.task_id, err);
+                }
+                drop(slot);
+                continue;
+            }
         };
 
-        let mut code_result: crate::code_result::ActiveModel = code_result.insert(&*task.db).await.unwrap().into();
+        let db = db.clone();
+        let config = config.clone();
+        let pool = pool.clone();
+        let released = released.clone();
+        tokio::spawn(async move {
+            // Aborted when dropped, so the lease also stops being renewed when `process` panics.
+            let heartbeat = {
+                let db = db.clone();
+                let result_id = code_result.id;
+                AbortOnDropHandle::new(tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(lease / 3);
+                    loop {
+                        interval.tick().await;
+                        match queue::heartbeat(&db, result_id, worker_id).await {
+                            Ok(true) => {}
+                            Ok(false) => break,
+                            Err(err) => tracing::error!("Failed to refresh lease of result {}: {}", result_id, err),
+                        }
+                    }
+                }))
+            };
 
-        let docker = Docker::connect_with_defaults().unwrap();
+            let workflow_run_id = code_result.workflow_run_id;
+            process(&db, &config, &pool, code_result, node).await;
+            drop(heartbeat);
 
-        let _dependencies = match task.dependencies.is_empty() {
-            true => None,
-            false => Some(&task.dependencies),
-        };
+            // Enqueues the steps this one unblocked, before the slot frees up to claim them.
+            if let Some(run_id) = workflow_run_id
+                && let Err(err) = workflow_engine::advance(&db, run_id).await
+            {
+                tracing::error!("Failed to advance workflow run {}: {}", run_id, err);
+            }
 
-        let command = node.get_command(&task.args, _dependencies);
+            drop(language_slot);
+            drop(slot);
+            released.notify_one();
+        });
+    }
+}
 
-        tracing::info!("Command to run: {:?}", &command);
+/// How long a task waiting for the image of its dependencies stays in the queue before it checks again.
+const DEPENDENCY_BUILD_WAIT: Duration = Duration::from_secs(5);
 
-        let container = ContainerCreateBody {
-            working_dir: Some("/app/".to_string()),
-            image: Some(node.language.get_image_name().to_string()),
-            cmd: Some(command),
-            // cmd: Some(vec!["tail".to_string(), "-f".to_string(), "/dev/null".to_string()]),
-            ..Default::default()
-        };
+async fn process(db: &DatabaseConnection, config: &Config, pool: &Arc<WarmPool>, code_result: CodeResultModel, node: CodeNodeModel) {
+    let task_id = code_result.task_id;
+    tracing::info!("Processing code node with ID: {} (attempt {})", task_id, code_result.attempt);
 
-        let container = docker.create_container(Some(CreateContainerOptions::default()), container).await.unwrap();
+    let timeout = code_result
+        .timeout_seconds
+        .or(node.timeout_seconds)
+        .map(|timeout| Duration::from_secs(timeout as u64))
+        .unwrap_or(Duration::from_secs(config.default_timeout_seconds));
 
-        let file = File::open(node.to_tar().await).map_ok(ReaderStream::new).try_flatten_stream();
-        let body_stream = body_try_stream(file);
+    let dependencies = dependencies::merge(&node.dependencies.0, &code_result.dependencies.0);
 
-        let _upload_options = UploadToContainerOptions {
-            path: "/app/".to_string(),
-            ..Default::default()
-        };
+    let Some(runtime) = config.runtimes.get(&node.language) else {
+        tracing::error!("Task {} uses unknown language '{}'", task_id, node.language);
+        let message = format!("No runtime is registered for language '{}'", node.language);
+        finish(db, &node.retry_policy, &code_result, Outcome::failed(None, message)).await;
+        return;
+    };
 
-        docker
-            .upload_to_container(&container.id, Some(_upload_options), body_stream)
+    let execution = match Docker::connect_with_defaults() {
+        Ok(docker) => {
+            execute(
+                db,
+                config,
+                &docker,
+                pool,
+                runtime.as_ref(),
+                &node,
+                code_result.id,
+                task_id,
+                &code_result.args.0,
+                &dependencies,
+                timeout,
+            )
             .await
-            .unwrap();
+        }
+        Err(err) => Err(err.into()),
+    };
 
-        code_result.status = Set(crate::enums::ResultStatus::Running);
-        let mut code_result: crate::code_result::ActiveModel = code_result.update(&*task.db).await.unwrap().into();
+    let execution = match execution {
+        Ok(Run::Executed(execution)) => execution,
+        Ok(Run::Cancelled) => {
+            tracing::info!("Task {} was cancelled before it started", task_id);
+            return;
+        }
+        Ok(Run::Postponed) => {
+            tracing::info!("Task {} waits for the image of its dependencies, putting it back", task_id);
+            if let Some(worker_id) = code_result.worker_id
+                && let Err(err) = queue::release(db, code_result.id, worker_id, DEPENDENCY_BUILD_WAIT).await
+            {
+                tracing::error!("Failed to put task {} back in the queue: {}", task_id, err);
+            }
+            return;
+        }
+        Ok(Run::Rejected(error_reason, message)) => {
+            tracing::warn!("Task {} cannot run: {}", task_id, message);
+            finish(db, &node.retry_policy, &code_result, Outcome::failed(Some(error_reason), message)).await;
+            return;
+        }
+        Err(err) => {
+            // Failures of seraph itself are recorded on the task rather than taking the worker down.
+            tracing::error!("Failed to run task {}: {}", task_id, err);
+            let outcome = Outcome::failed(Some(ErrorReason::InternalError), err.to_string());
+            finish(db, &node.retry_policy, &code_result, outcome).await;
+            return;
+        }
+    };
 
-        docker
-            .start_container(&container.id, Some(StartContainerOptions::default()))
-            .await
-            .unwrap();
-
-        let container_results = docker
-            .wait_container(&container.id, Some(WaitContainerOptions::default()))
-            .collect::<Vec<_>>()
-            .await;
-
-        let exit_code = container_results
-            .into_iter()
-            .filter_map(Result::ok)
-            .find_map(|result| Some(result.status_code))
-            .unwrap_or(1); // Default to non-zero if no status code is found
-
-        let logs = docker
-            .logs(
-                &container.id,
-                Some(LogsOptions {
-                    follow: true,
-                    stdout: true,
-                    stderr: true,
-                    ..Default::default()
-                }),
-            )
-            .collect::<Vec<_>>()
-            .await;
+    let (status, error_reason, error_message, return_value) = match execution.exit_code {
+        None => (ResultStatus::Timeout, None, None, None),
+        Some(0) => match decode_return_value(runtime.as_ref(), &node.output_type, execution.return_value) {
+            Ok(value) => (ResultStatus::Success, None, None, Some(value)),
+            Err(message) => (ResultStatus::Error, Some(ErrorReason::OutputTypeMismatch), Some(message), None),
+        },
+        Some(_) if execution.oom_killed => (ResultStatus::Error, Some(ErrorReason::OutOfMemory), None, None),
+        Some(_) => (ResultStatus::Error, Some(ErrorReason::NonZeroExit), None, None),
+    };
 
-        let output: String = logs
-            .into_iter()
-            .filter_map(Result::ok)
-            .flat_map(|log| log.into_bytes().into_iter().map(|b: u8| b as char))
-            .collect();
+    // Logs collected are partial when the task timed out, keep them for debugging.
+    let outcome = Outcome {
+        status,
+        error_reason,
+        error_message,
+        stdout: Some(String::from_utf8_lossy(&execution.stdout).into_owned()),
+        stderr: Some(String::from_utf8_lossy(&execution.stderr).into_owned()),
+        return_value,
+        exit_code: execution.exit_code,
+        resolved_dependencies: execution.resolved_dependencies,
+    };
+
+    if finish(db, &node.retry_policy, &code_result, outcome).await {
+        tracing::info!("Successfully processed code node with ID: {}", task_id);
+    }
+}
+
+/// How an attempt at running a task ended.
+struct Outcome {
+    status: ResultStatus,
+    error_reason: Option<ErrorReason>,
+    error_message: Option<String>,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    return_value: Option<Json>,
+    exit_code: Option<i64>,
+    resolved_dependencies: Option<Vec<String>>,
+}
+
+impl Outcome {
+    /// An attempt that failed before the code ran.
+    fn failed(error_reason: Option<ErrorReason>, error_message: String) -> Self {
+        Self {
+            status: ResultStatus::Error,
+            error_reason,
+            error_message: Some(error_message),
+            stdout: None,
+            stderr: None,
+            return_value: None,
+            exit_code: None,
+            resolved_dependencies: None,
+        }
+    }
+}
 
-        docker
-            .remove_container(&container.id, Some(RemoveContainerOptions::default()))
+/// What a container run produced.
+struct Execution {
+    /// `None` means the container outlived its timeout and was killed.
+    exit_code: Option<i64>,
+    oom_killed: bool,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    return_value: Option<String>,
+    resolved_dependencies: Option<Vec<String>>,
+}
+
+/// What became of a task handed to `execute`.
+enum Run {
+    Executed(Execution),
+    /// Cancelled before it started.
+    Cancelled,
+    /// The image of its dependencies is being built by another process, the task has to wait for it.
+    Postponed,
+    /// Cannot run in its current configuration, so retrying would not help.
+    Rejected(ErrorReason, String),
+}
+
+impl From<Option<Execution>> for Run {
+    fn from(execution: Option<Execution>) -> Self {
+        execution.map_or(Run::Cancelled, Run::Executed)
+    }
+}
+
+/// Creates the container of a task and runs it.
+///
+/// The container is removed whatever the outcome. Languages with a warm pool run in a pooled container instead.
+#[allow(clippy::too_many_arguments)]
+async fn execute(
+    db: &DatabaseConnection,
+    config: &Config,
+    docker: &Docker,
+    pool: &Arc<WarmPool>,
+    runtime: &dyn LanguageRuntime,
+    node: &CodeNodeModel,
+    result_id: i32,
+    task_id: uuid::Uuid,
+    args: &[String],
+    dependencies: &[String],
+    timeout: Duration,
+) -> Result<Run, SeraphError> {
+    // The files of the revision being run, `node` is already the pinned one.
+    let files = CodeNodeVersionFile::find()
+        .filter(CodeNodeVersionFileColumn::CodeNodeId.eq(node.id))
+        .filter(CodeNodeVersionFileColumn::Version.eq(node.version))
+        .all(db)
+        .await?;
+
+    // Installing at runtime needs to download the packages, which a sandboxed container without a network cannot do.
+    let can_install = dependencies.is_empty() || node.execution_profile == ExecutionProfile::Trusted || config.sandbox_network.is_some();
+
+    // Dependencies preinstalled in a cached image are not installed again by the harness.
+    let (image, dependencies) = match image_cache::resolve(db, docker, config, runtime, dependencies).await? {
+        Resolution::Image(image) => (image, &[][..]),
+        Resolution::Building | Resolution::Install if can_install => (runtime.image().to_string(), dependencies),
+        Resolution::Building => return Ok(Run::Postponed),
+        Resolution::Install => {
+            let message = "Dependencies of sandboxed runs without a network must be preinstalled, but caching is disabled, \
+                           the runtime cannot preinstall them, or building their image failed";
+            return Ok(Run::Rejected(ErrorReason::DependenciesUnavailable, message.to_string()));
+        }
+    };
+
+    let command = runtime.build_command(node, args, dependencies);
+
+    tracing::info!("Command to run in {}: {:?}", image, &command);
+
+    let limits = ResourceLimits::resolve(&RequestedLimits::of(node), config);
+    tracing::debug!("Resource limits for task {}: {:?}", task_id, limits);
+
+    if WarmPool::enabled(config, &node.language) {
+        let key = PoolKey {
+            language: node.language.clone(),
+            image,
+            profile: node.execution_profile.clone(),
+            limits,
+            code_node_id: None,
+        }
+        .bound_to(node.id);
+        let container = pool.acquire(docker, &key).await?;
+
+        let (execution, dirty) = match queue::attach_container(db, result_id, &container.id).await {
+            Ok(true) => match run_exec(docker, runtime, node, &files, &container, command, task_id, timeout).await {
+                Ok((execution, dirty)) => (Ok(Some(execution)), dirty),
+                Err(err) => (Err(err), true),
+            },
+            Ok(false) => (Ok(None), false),
+            Err(err) => (Err(err.into()), false),
+        };
+
+        pool.release(docker, &key, container, dirty).await;
+        return execution.map(Run::from);
+    }
+
+    let mut container = ContainerCreateBody {
+        working_dir: Some(sandbox::WORK_DIR.to_string()),
+        image: Some(image),
+        cmd: Some(command),
+        labels: Some([("seraph.task_id".to_string(), task_id.to_string())].into()),
+        env: Some(vec![
+            format!("SERAPH_RESULT_PATH={}", sandbox::RESULT_FILE),
+            format!("SERAPH_DEPENDENCIES_PATH={}", sandbox::DEPENDENCIES_FILE),
+        ]),
+        host_config: Some(limits.host_config()),
+        ..Default::default()
+    };
+    sandbox::apply(&node.execution_profile, &limits, config, &mut container);
+
+    let container = docker.create_container(Some(CreateContainerOptions::default()), container).await?;
+
+    let execution = match queue::attach_container(db, result_id, &container.id).await {
+        Ok(true) => run_container(docker, runtime, node, &files, &container.id, task_id, timeout)
             .await
-            .unwrap();
+            .map(Some),
+        Ok(false) => Ok(None),
+        Err(err) => Err(err.into()),
+    };
+
+    remove_container(docker, &container.id).await;
+    execution.map(Run::from)
+}
+
+async fn run_container(
+    docker: &Docker,
+    runtime: &dyn LanguageRuntime,
+    node: &CodeNodeModel,
+    files: &[CodeNodeVersionFileModel],
+    container_id: &str,
+    task_id: uuid::Uuid,
+    timeout: Duration,
+) -> Result<Execution, SeraphError> {
+    upload_code(docker, runtime, node, files, container_id, "").await?;
+
+    docker.start_container(container_id, Some(StartContainerOptions::default())).await?;
+
+    let container_results = docker
+        .wait_container(container_id, Some(WaitContainerOptions::default()))
+        .collect::<Vec<_>>();
+
+    let exit_code = match tokio::time::timeout(timeout, container_results).await {
+        Ok(container_results) => Some(
+            container_results
+                .into_iter()
+                .find_map(|result| match result {
+                    Ok(result) => Some(result.status_code),
+                    // Docker reports non-zero exits as errors, carrying the exit code.
+                    Err(DockerError::DockerContainerWaitError { code, .. }) => Some(code),
+                    Err(_) => None,
+                })
+                .unwrap_or(1), // Default to non-zero if no status code is found
+        ),
+        Err(_) => {
+            tracing::warn!("Task {} timed out after {:?}, killing container", task_id, timeout);
+            docker.kill_container(container_id, Some(KillContainerOptions::default())).await?;
+            None
+        }
+    };
+
+    let oom_killed = docker
+        .inspect_container(container_id, None::<InspectContainerOptions>)
+        .await
+        .ok()
+        .and_then(|inspect| inspect.state)
+        .and_then(|state| state.oom_killed)
+        .unwrap_or(false);
 
-        code_result.status = match exit_code {
-            0 => Set(crate::enums::ResultStatus::Success),
-            _ => Set(crate::enums::ResultStatus::Error),
+    let logs = docker
+        .logs(
+            container_id,
+            Some(LogsOptions {
+                follow: true,
+                stdout: true,
+                stderr: true,
+                ..Default::default()
+            }),
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    for log in logs.into_iter().filter_map(Result::ok) {
+        match log {
+            LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+            LogOutput::StdOut { message } | LogOutput::Console { message } => stdout.extend_from_slice(&message),
+            LogOutput::StdIn { .. } => {}
+        }
+    }
+
+    // The harness writes the return value to a dedicated file, so prints in user code cannot corrupt it.
+    let return_value = match exit_code {
+        Some(0) => read_container_file(docker, container_id, sandbox::RESULT_FILE).await,
+        _ => None,
+    };
+
+    // Reported before the function is called, so failed runs record what they ran with too.
+    let resolved_dependencies = parse_dependencies(task_id, read_container_file(docker, container_id, sandbox::DEPENDENCIES_FILE).await);
+
+    Ok(Execution {
+        exit_code,
+        oom_killed,
+        stdout,
+        stderr,
+        return_value,
+        resolved_dependencies,
+    })
+}
+
+/// Runs a task in a warm container, in a directory of its own, then resets the container.
+///
+/// Also returns whether the container is dirty, i.e. should not run another task: the run timed out and it was
+/// killed, the code ran out of memory, or the container could not be reset.
+#[allow(clippy::too_many_arguments)]
+async fn run_exec(
+    docker: &Docker,
+    runtime: &dyn LanguageRuntime,
+    node: &CodeNodeModel,
+    files: &[CodeNodeVersionFileModel],
+    container: &WarmContainer,
+    command: Vec<String>,
+    task_id: uuid::Uuid,
+    timeout: Duration,
+) -> Result<(Execution, bool), SeraphError> {
+    let run_dir = format!("{}{}", sandbox::WORK_DIR, task_id);
+    let result_file = format!("{}/{}", run_dir, file_name(sandbox::RESULT_FILE));
+    let dependencies_file = format!("{}/{}", run_dir, file_name(sandbox::DEPENDENCIES_FILE));
+
+    upload_code(docker, runtime, node, files, &container.id, &format!("{}/", task_id)).await?;
+
+    let env = vec![
+        format!("SERAPH_RESULT_PATH={}", result_file),
+        format!("SERAPH_DEPENDENCIES_PATH={}", dependencies_file),
+    ];
+    let oom_kills_before = oom_kills(docker, &container.id).await;
+    let (exit_code, stdout, stderr) = exec(docker, &container.id, command, &run_dir, env, timeout).await?;
+
+    let Some(exit_code) = exit_code else {
+        tracing::warn!("Task {} timed out after {:?}, killing container", task_id, timeout);
+        docker.kill_container(&container.id, Some(KillContainerOptions::default())).await?;
+
+        let execution = Execution {
+            exit_code: None,
+            oom_killed: false,
+            stdout,
+            stderr,
+            return_value: None,
+            resolved_dependencies: None,
         };
+        return Ok((execution, true));
+    };
+
+    // `OOMKilled` only covers the idling process, the cgroup counts the processes of the code killed for memory.
+    let oom_killed = match (oom_kills_before, oom_kills(docker, &container.id).await) {
+        (Some(before), Some(after)) if after > before => true,
+        _ => docker
+            .inspect_container(&container.id, None::<InspectContainerOptions>)
+            .await
+            .ok()
+            .and_then(|inspect| inspect.state)
+            .and_then(|state| state.oom_killed)
+            .unwrap_or(false),
+    };
+
+    let return_value = match exit_code {
+        0 => read_container_file(docker, &container.id, &result_file).await,
+        _ => None,
+    };
+    let resolved_dependencies = parse_dependencies(task_id, read_container_file(docker, &container.id, &dependencies_file).await);
+
+    let reset = match exec(docker, &container.id, reset_command(), sandbox::WORK_DIR, vec![], CLEANUP_TIMEOUT).await {
+        Ok((Some(0), _, _)) => true,
+        Ok((_, _, stderr)) => {
+            tracing::warn!(
+                "Failed to reset warm container {} after task {}: {}",
+                container.id,
+                task_id,
+                String::from_utf8_lossy(&stderr)
+            );
+            false
+        }
+        Err(err) => {
+            tracing::warn!("Failed to reset warm container {} after task {}: {}", container.id, task_id, err);
+            false
+        }
+    };
+
+    let execution = Execution {
+        exit_code: Some(exit_code),
+        oom_killed,
+        stdout,
+        stderr,
+        return_value,
+        resolved_dependencies,
+    };
+    Ok((execution, oom_killed || !reset))
+}
+
+/// How long reading the memory counters of a warm container may take.
+const OOM_KILLS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of processes of a container the OOM killer ended so far, from its memory cgroup. `None` when it cannot be
+/// read, e.g. on kernels older than 4.13.
+async fn oom_kills(docker: &Docker, container_id: &str) -> Option<u64> {
+    // cgroup v2 first, then the v1 memory controller. Both list the count as `oom_kill <n>`.
+    let script = "cat /sys/fs/cgroup/memory.events 2>/dev/null || cat /sys/fs/cgroup/memory/memory.oom_control";
+    let command = ["sh", "-c", script].map(str::to_string).to_vec();
+
+    let (exit_code, stdout, _) = exec(docker, container_id, command, sandbox::WORK_DIR, vec![], OOM_KILLS_TIMEOUT)
+        .await
+        .ok()?;
+    if exit_code != Some(0) {
+        return None;
+    }
+
+    String::from_utf8_lossy(&stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+/// How long resetting a warm container after a task may take.
+const CLEANUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Command that leaves a warm container as it was started: it kills every process but the idling one and deletes
+/// everything written to `/tmp` and `WORK_DIR`.
+///
+/// Runs as the same user as the code, so it can kill whatever the code started. The empty task directories made by
+/// the uploader are kept, `WORK_DIR` is sticky and they are not the user's to remove in sandboxed containers.
+fn reset_command() -> Vec<String> {
+    let script = format!(
+        "kill -9 -1 2>/dev/null; find /tmp {} -mindepth 1 -depth ! \\( -type d -empty -user 0 \\) -delete",
+        sandbox::WORK_DIR
+    );
+    ["sh", "-c", &script].map(str::to_string).to_vec()
+}
+
+/// Runs `command` in a running container, returns its exit code and output.
+///
+/// The exit code is `None` when the command outlived `timeout`, it is left running and the output is partial.
+async fn exec(
+    docker: &Docker,
+    container_id: &str,
+    command: Vec<String>,
+    working_dir: &str,
+    env: Vec<String>,
+    timeout: Duration,
+) -> Result<(Option<i64>, Vec<u8>, Vec<u8>), SeraphError> {
+    let config = ExecConfig {
+        cmd: Some(command),
+        env: Some(env),
+        working_dir: Some(working_dir.to_string()),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        ..Default::default()
+    };
+    let exec = docker.create_exec(container_id, config).await?;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+
+    let StartExecResults::Attached { mut output, .. } = docker.start_exec(&exec.id, None::<StartExecOptions>).await? else {
+        return Err(SeraphError::Internal("Exec started detached".to_string()));
+    };
+
+    let collected = tokio::time::timeout(timeout, async {
+        while let Some(log) = output.next().await {
+            match log? {
+                LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+                LogOutput::StdOut { message } | LogOutput::Console { message } => stdout.extend_from_slice(&message),
+                LogOutput::StdIn { .. } => {}
+            }
+        }
+        Ok::<_, SeraphError>(())
+    })
+    .await;
+
+    match collected {
+        Ok(collected) => collected?,
+        Err(_) => return Ok((None, stdout, stderr)),
+    }
+
+    // The output closes right before the exec is reported as finished.
+    loop {
+        let inspect = docker.inspect_exec(&exec.id).await?;
+        if inspect.running != Some(true) {
+            return Ok((Some(inspect.exit_code.unwrap_or(1)), stdout, stderr));
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Uploads the code of a task to the working directory, under `directory`.
+async fn upload_code(
+    docker: &Docker,
+    runtime: &dyn LanguageRuntime,
+    node: &CodeNodeModel,
+    files: &[CodeNodeVersionFileModel],
+    container_id: &str,
+    directory: &str,
+) -> Result<(), SeraphError> {
+    let file = File::open(node.to_tar(runtime, files, directory).await?)
+        .map_ok(ReaderStream::new)
+        .try_flatten_stream();
+    let body_stream = body_try_stream(file);
+
+    let _upload_options = UploadToContainerOptions {
+        path: sandbox::WORK_DIR.to_string(),
+        ..Default::default()
+    };
+
+    docker.upload_to_container(container_id, Some(_upload_options), body_stream).await?;
+    Ok(())
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Records an attempt at a task, then stores its outcome on the result or queues the task again when `policy` retries it.
+///
+/// Returns whether the outcome was stored, which it is not when the task was cancelled while running or retried.
+/// When the database is unavailable the task stays Running, it is put back in the queue once its lease expires.
+async fn finish(db: &DatabaseConnection, policy: &RetryPolicy, code_result: &CodeResultModel, outcome: Outcome) -> bool {
+    match store_attempt(db, policy, code_result, outcome).await {
+        Ok(stored) => stored,
+        Err(err) => {
+            tracing::error!("Failed to store result {}: {}", code_result.id, err);
+            false
+        }
+    }
+}
+
+async fn store_attempt(db: &DatabaseConnection, policy: &RetryPolicy, code_result: &CodeResultModel, outcome: Outcome) -> Result<bool, DbErr> {
+    let now = TimeDateTimeWithTimeZone::now_utc();
+    let backoff = policy.backoff(code_result.attempt, FailureClass::of(&outcome.status, outcome.error_reason.as_ref()));
+
+    let update = match backoff {
+        // Back in the queue as if it was never claimed, it is not claimed again before its backoff has passed.
+        Some(backoff) => CodeResultActiveModel {
+            status: Set(ResultStatus::Pending),
+            attempt: Set(code_result.attempt + 1),
+            available_at: Set(Some(now + backoff)),
+            worker_id: Set(None),
+            started_at: Set(None),
+            heartbeat_at: Set(None),
+            container_id: Set(None),
+            ..Default::default()
+        },
+        None => CodeResultActiveModel {
+            status: Set(outcome.status.clone()),
+            error_reason: Set(outcome.error_reason.clone()),
+            error_message: Set(outcome.error_message.clone()),
+            stdout: Set(outcome.stdout.clone()),
+            stderr: Set(outcome.stderr.clone()),
+            return_value: Set(outcome.return_value),
+            exit_code: Set(outcome.exit_code),
+            resolved_dependencies: Set(outcome.resolved_dependencies.map(StringList)),
+            finished_at: Set(Some(now)),
+            ..Default::default()
+        },
+    };
+
+    let txn = db.begin().await?;
+
+    let updated = CodeResult::update_many()
+        .set(update)
+        .filter(CodeResultColumn::Id.eq(code_result.id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Running))
+        .exec(&txn)
+        .await?;
+
+    if updated.rows_affected == 0 {
+        tracing::info!("Result {} was cancelled while running", code_result.id);
+        return Ok(false);
+    }
+
+    CodeResultAttemptActiveModel {
+        code_result_id: Set(code_result.id),
+        attempt: Set(code_result.attempt),
+        status: Set(outcome.status),
+        error_reason: Set(outcome.error_reason),
+        error_message: Set(outcome.error_message),
+        exit_code: Set(outcome.exit_code),
+        stdout: Set(outcome.stdout),
+        stderr: Set(outcome.stderr),
+        started_at: Set(code_result.started_at),
+        finished_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+
+    txn.commit().await?;
+
+    match backoff {
+        Some(backoff) => {
+            tracing::warn!(
+                "Attempt {} of task {} failed, retrying in {:?}",
+                code_result.attempt,
+                code_result.task_id,
+                backoff
+            );
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
+
+/// Decodes what the harness wrote with the node's runtime and checks it against the declared output type.
+fn decode_return_value(runtime: &dyn LanguageRuntime, output_type: &OutputType, raw: Option<String>) -> Result<Json, String> {
+    let raw = raw.ok_or_else(|| "The function did not write a return value".to_string())?;
+    let value = runtime.decode_output(&raw)?;
+
+    output_type.validate(&value)?;
+    Ok(value)
+}
+
+fn parse_dependencies(task_id: uuid::Uuid, raw: Option<String>) -> Option<Vec<String>> {
+    match serde_json::from_str(&raw?) {
+        Ok(resolved) => Some(resolved),
+        Err(err) => {
+            tracing::warn!("Task {} reported invalid dependencies: {}", task_id, err);
+            None
+        }
+    }
+}
+
+/// Reads a file the harness wrote, such as `sandbox::RESULT_FILE`, the container must still exist.
+async fn read_container_file(docker: &Docker, container_id: &str, path: &str) -> Option<String> {
+    let options = DownloadFromContainerOptions { path: path.to_string() };
+
+    let mut archive = Vec::new();
+    let mut chunks = docker.download_from_container(container_id, Some(options));
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(chunk) => archive.extend_from_slice(&chunk),
+            Err(err) => {
+                // Missing files end up here too, e.g. runtimes that do not report their dependencies.
+                tracing::debug!("Failed to read {} of container {}: {}", path, container_id, err);
+                return None;
+            }
+        }
+    }
+
+    // Docker returns the file wrapped in a tar archive.
+    let mut entries = tokio_tar::Archive::new(archive.as_slice()).entries().ok()?;
+    let mut entry = entries.next().await?.ok()?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content).await.ok()?;
+
+    Some(content)
+}
 
-        code_result.output = Set(Some(output.clone()));
-        code_result.update(&*task.db).await.unwrap();
+/// Removes a finished container and its anonymous volumes, it may already be gone when the task was cancelled.
+pub async fn remove_container(docker: &Docker, container_id: &str) {
+    let options = RemoveContainerOptions {
+        force: true,
+        v: true,
+        ..Default::default()
+    };
 
-        tracing::info!("Successfully processed code node with ID: {}", task.id);
+    if let Err(err) = docker.remove_container(container_id, Some(options)).await {
+        tracing::warn!("Failed to remove container {}: {}", container_id, err);
     }
 }
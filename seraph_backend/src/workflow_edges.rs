@@ -0,0 +1,41 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Passes the return value of `from_step` as argument `argument` of `to_step`, which runs once `from_step` succeeded.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "workflow_edges")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub workflow_id: i32,
+
+    pub from_step: String,
+    pub to_step: String,
+    /// Position of the value in the arguments of `to_step`, starting at 0.
+    pub argument: i32,
+    /// Branch of a condition `from_step` this edge is followed on, unset for edges from other steps.
+    #[serde(default)]
+    pub branch: Option<bool>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-workflow_edges-workflow_id",
+        belongs_to = "super::workflows::Entity",
+        from = "Column::WorkflowId",
+        to = "super::workflows::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Workflow,
+}
+
+impl Related<super::workflows::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Workflow.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,563 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::prelude::{Json, TimeDateTimeWithTimeZone};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+
+use crate::code_nodes::{Column as CodeNodeColumn, Entity as CodeNode};
+use crate::code_result::{Column as CodeResultColumn, Entity as CodeResult, Model as CodeResultModel};
+use crate::enums::ResultStatus;
+use crate::error::SeraphError;
+use crate::queue;
+use crate::worker::CodeNodeTask;
+use crate::workflow_edges::Model as Edge;
+use crate::workflow_runs::{
+    ActiveModel as WorkflowRunActiveModel, Column as WorkflowRunColumn, Entity as WorkflowRun, Graph, Model as WorkflowRunModel,
+};
+use crate::workflows::{Model as Workflow, Step, StepKind};
+
+/// Checks the steps and edges of a workflow, returns the steps in topological order.
+///
+/// Steps that do not depend on each other keep the order they were declared in.
+pub fn validate(steps: &[Step], edges: &[Edge]) -> Result<Vec<Step>, String> {
+    if steps.is_empty() {
+        return Err("A workflow needs at least one step".to_string());
+    }
+
+    let mut names = HashSet::new();
+    for step in steps {
+        if step.name.is_empty() {
+            return Err("Step names must not be empty".to_string());
+        }
+        if !names.insert(step.name.as_str()) {
+            return Err(format!("Duplicate step name: '{}'", step.name));
+        }
+        validate_step(step, edges)?;
+    }
+
+    let mut arguments = HashSet::new();
+    for edge in edges {
+        let Some(to) = steps.iter().find(|step| step.name == edge.to_step) else {
+            return Err(format!("Edge leads to unknown step '{}'", edge.to_step));
+        };
+        let Some(from) = steps.iter().find(|step| step.name == edge.from_step) else {
+            return Err(format!("Edge comes from unknown step '{}'", edge.from_step));
+        };
+
+        let arity = to.args.len() + edges.iter().filter(|other| other.to_step == to.name).count();
+        if edge.argument < 0 || edge.argument as usize >= arity {
+            return Err(format!(
+                "Argument {} of step '{}' is out of range, it takes {} arguments",
+                edge.argument, to.name, arity
+            ));
+        }
+        if !arguments.insert((edge.to_step.as_str(), edge.argument)) {
+            return Err(format!("Argument {} of step '{}' has several incoming edges", edge.argument, to.name));
+        }
+
+        match (from.kind, edge.branch) {
+            (StepKind::Condition, None) => return Err(format!("Edges from condition step '{}' need a branch", from.name)),
+            (StepKind::Condition, Some(_)) => {}
+            (_, Some(_)) => return Err(format!("Only edges from condition steps have a branch, '{}' is not one", from.name)),
+            (_, None) => {}
+        }
+        if from.kind == StepKind::Map && to.kind != StepKind::Join {
+            return Err(format!("Edges from map step '{}' must lead to a join step", from.name));
+        }
+    }
+
+    let mut remaining: Vec<&Step> = steps.iter().collect();
+    let mut sorted: Vec<Step> = Vec::with_capacity(steps.len());
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|step| {
+            edges
+                .iter()
+                .filter(|edge| edge.to_step == step.name)
+                .all(|edge| sorted.iter().any(|done| done.name == edge.from_step))
+        });
+
+        let Some(ready) = ready else {
+            let names: Vec<&str> = remaining.iter().map(|step| step.name.as_str()).collect();
+            return Err(format!("Edges form a cycle, steps {} can never run", names.join(", ")));
+        };
+        sorted.push(remaining.remove(ready).clone());
+    }
+
+    Ok(sorted)
+}
+
+/// Checks the settings and inputs a step needs for its kind.
+fn validate_step(step: &Step, edges: &[Edge]) -> Result<(), String> {
+    let incoming: Vec<&Edge> = edges.iter().filter(|edge| edge.to_step == step.name).collect();
+    let has_argument = |argument: i32| incoming.iter().any(|edge| edge.argument == argument);
+
+    match step.kind {
+        StepKind::Code | StepKind::Map if step.code_node_id.is_none() => {
+            return Err(format!("Step '{}' needs a code_node_id", step.name));
+        }
+        StepKind::Condition | StepKind::Join if step.code_node_id.is_some() || step.version.is_some() || !step.args.is_empty() => {
+            return Err(format!(
+                "Step '{}' runs no code node, it takes no code_node_id, version or args",
+                step.name
+            ));
+        }
+        _ => {}
+    }
+
+    if step.kind != StepKind::Map && step.concurrency.is_some() {
+        return Err(format!("Only map steps have a concurrency, '{}' is not one", step.name));
+    }
+
+    match step.kind {
+        StepKind::Code => Ok(()),
+        StepKind::Condition if !has_argument(0) || incoming.len() > 2 => Err(format!(
+            "Condition step '{}' takes a boolean as argument 0, and optionally the value it passes on as argument 1",
+            step.name
+        )),
+        StepKind::Map if !has_argument(0) => Err(format!("Map step '{}' takes the array to map over as argument 0", step.name)),
+        StepKind::Map if step.concurrency == Some(0) => Err(format!("Concurrency of map step '{}' must be positive", step.name)),
+        StepKind::Join if incoming.is_empty() => Err(format!("Join step '{}' has nothing to collect", step.name)),
+        _ => Ok(()),
+    }
+}
+
+/// Arguments of `step`, the values of its incoming edges at their positions and its own `args` in the others.
+fn arguments(step: &Step, mut inputs: HashMap<i32, Json>) -> Vec<String> {
+    let mut args = step.args.iter();
+    (0..(step.args.len() + inputs.len()) as i32)
+        .filter_map(|position| inputs.remove(&position).map(|value| value.to_string()).or_else(|| args.next().cloned()))
+        .collect()
+}
+
+/// Starts a run of `workflow` and enqueues the steps without incoming edges.
+///
+/// Steps without a version are pinned to the current version of their node.
+pub async fn start<C: ConnectionTrait>(db: &C, workflow: &Workflow, edges: Vec<Edge>) -> Result<WorkflowRunModel, SeraphError> {
+    let mut steps = validate(&workflow.steps.0, &edges).map_err(SeraphError::InvalidInput)?;
+
+    let nodes: HashMap<i32, i32> = CodeNode::find()
+        .select_only()
+        .column(CodeNodeColumn::Id)
+        .column(CodeNodeColumn::Version)
+        .filter(CodeNodeColumn::Id.is_in(steps.iter().filter_map(|step| step.code_node_id)))
+        .into_tuple::<(i32, i32)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    for step in &mut steps {
+        let Some(code_node_id) = step.code_node_id else {
+            continue;
+        };
+        let Some(version) = nodes.get(&code_node_id) else {
+            return Err(SeraphError::InvalidInput(format!(
+                "Code node {} of step '{}' no longer exists",
+                code_node_id, step.name
+            )));
+        };
+        step.version = step.version.or(Some(*version));
+    }
+
+    let run = WorkflowRunActiveModel {
+        run_id: Set(uuid::Uuid::new_v4()),
+        workflow_id: Set(workflow.id),
+        status: Set(ResultStatus::Running),
+        graph: Set(Graph { steps, edges }),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    progress(db, &run).await?;
+    Ok(run)
+}
+
+/// Moves a run forward after one of its steps finished, enqueueing the steps it unblocked or finishing the run.
+///
+/// Safe to call at any time, it only acts on what the results of the steps say.
+pub async fn advance(db: &DatabaseConnection, run_id: i32) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+
+    // Locked so steps finishing at the same time do not both enqueue the steps they lead to.
+    let run = WorkflowRun::find_by_id(run_id).lock_exclusive().one(&txn).await?;
+    if let Some(run) = run.filter(|run| run.status == ResultStatus::Running) {
+        progress(&txn, &run).await?;
+    }
+
+    txn.commit().await
+}
+
+/// Advances every running workflow run, covering steps whose process stopped before advancing their run.
+pub async fn recover(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let running: Vec<i32> = WorkflowRun::find()
+        .select_only()
+        .column(WorkflowRunColumn::Id)
+        .filter(WorkflowRunColumn::Status.eq(ResultStatus::Running))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    for run_id in running {
+        advance(db, run_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Where a step of a run stands.
+enum Outcome {
+    /// Waiting for the steps before it, or for its own tasks.
+    Pending,
+    /// A condition before it took the other branch.
+    Skipped,
+    Done(Json),
+    /// A condition took `branch`, passing on the value.
+    Routed {
+        branch: bool,
+        value: Json,
+    },
+    /// Results of a map step, one per element.
+    Mapped(Vec<Json>),
+}
+
+/// Value an edge carries, `None` while it is not known yet.
+enum Input {
+    Pending,
+    Skipped,
+    Values(Vec<Json>),
+}
+
+fn input(edge: &Edge, outcomes: &HashMap<&str, Outcome>) -> Input {
+    match outcomes.get(edge.from_step.as_str()) {
+        None | Some(Outcome::Pending) => Input::Pending,
+        Some(Outcome::Skipped) => Input::Skipped,
+        Some(Outcome::Done(value)) => Input::Values(vec![value.clone()]),
+        Some(Outcome::Routed { branch, value }) if edge.branch == Some(*branch) => Input::Values(vec![value.clone()]),
+        Some(Outcome::Routed { .. }) => Input::Skipped,
+        Some(Outcome::Mapped(values)) => Input::Values(values.clone()),
+    }
+}
+
+async fn progress<C: ConnectionTrait>(db: &C, run: &WorkflowRunModel) -> Result<(), DbErr> {
+    let results = CodeResult::find().filter(CodeResultColumn::WorkflowRunId.eq(run.id)).all(db).await?;
+
+    // A failed task fails the run, the steps depending on it never run.
+    let failed = results
+        .iter()
+        .find(|result| matches!(result.status, ResultStatus::Error | ResultStatus::Timeout | ResultStatus::Cancelled));
+    if let Some(failed) = failed {
+        let status = match failed.status {
+            ResultStatus::Cancelled => ResultStatus::Cancelled,
+            _ => ResultStatus::Error,
+        };
+        let step = failed.step.as_deref().unwrap_or_default();
+        let message = match failed.map_index {
+            Some(index) => format!("Element {} of step '{}' ended with status {}", index, step, failed.status),
+            None => format!("Step '{}' ended with status {}", step, failed.status),
+        };
+        return fail(db, run, status, message).await;
+    }
+
+    let mut tasks = Vec::new();
+    let mut outcomes: HashMap<&str, Outcome> = HashMap::new();
+
+    // Steps are in topological order, the outcomes of the steps before each one are known.
+    for step in &run.graph.steps {
+        let mut inputs = Vec::new();
+        let mut pending = false;
+        let mut skipped = false;
+        for edge in run.graph.edges.iter().filter(|edge| edge.to_step == step.name) {
+            match input(edge, &outcomes) {
+                Input::Pending => pending = true,
+                Input::Skipped => skipped = true,
+                Input::Values(values) => inputs.push((edge.argument, values)),
+            }
+        }
+        inputs.sort_by_key(|(argument, _)| *argument);
+
+        // Joins merge branches, they only need one of their inputs.
+        let outcome = if pending {
+            Outcome::Pending
+        } else if skipped && (step.kind != StepKind::Join || inputs.is_empty()) {
+            Outcome::Skipped
+        } else {
+            let step_results: Vec<&CodeResultModel> = results
+                .iter()
+                .filter(|result| result.step.as_deref() == Some(step.name.as_str()))
+                .collect();
+
+            match evaluate(run, step, inputs, &step_results, &mut tasks) {
+                Ok(outcome) => outcome,
+                Err(message) => return fail(db, run, ResultStatus::Error, message).await,
+            }
+        };
+        outcomes.insert(&step.name, outcome);
+    }
+
+    if outcomes.values().all(|outcome| !matches!(outcome, Outcome::Pending)) {
+        return finish(db, run, ResultStatus::Success, None).await;
+    }
+
+    if tasks.is_empty() {
+        return Ok(());
+    }
+
+    // Deleting a node deletes its results too, a run it was part of cannot go on.
+    let existing: HashSet<i32> = CodeNode::find()
+        .select_only()
+        .column(CodeNodeColumn::Id)
+        .filter(CodeNodeColumn::Id.is_in(tasks.iter().map(|task: &CodeNodeTask| task.node_id)))
+        .into_tuple::<i32>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    for task in tasks {
+        let step = task.step.as_deref().unwrap_or_default();
+        if !existing.contains(&task.node_id) {
+            let message = format!("Code node {} of step '{}' was deleted", task.node_id, step);
+            return fail(db, run, ResultStatus::Error, message).await;
+        }
+
+        queue::enqueue(db, &task).await?;
+        tracing::info!("Enqueued step '{}' of workflow run {}", step, run.run_id);
+    }
+
+    Ok(())
+}
+
+/// Outcome of a step whose inputs are known, adding the tasks it still has to run to `tasks`.
+///
+/// Fails with a message when the inputs do not fit the step.
+fn evaluate(
+    run: &WorkflowRunModel,
+    step: &Step,
+    inputs: Vec<(i32, Vec<Json>)>,
+    results: &[&CodeResultModel],
+    tasks: &mut Vec<CodeNodeTask>,
+) -> Result<Outcome, String> {
+    let task = |inputs: HashMap<i32, Json>, map_index: Option<i32>| {
+        let code_node_id = step.code_node_id.expect("code and map steps have a code node");
+        let version = step.version.expect("steps of a run are pinned when it starts");
+        CodeNodeTask::new(code_node_id, version, arguments(step, inputs), vec![]).in_workflow(run.id, &step.name, map_index)
+    };
+
+    // Only joins read every value an edge from a map brings, validation keeps maps from leading elsewhere.
+    let mut single: HashMap<i32, Json> = inputs
+        .iter()
+        .filter_map(|(argument, values)| values.first().map(|value| (*argument, value.clone())))
+        .collect();
+
+    match step.kind {
+        StepKind::Code => match results.first() {
+            Some(result) if result.status == ResultStatus::Success => Ok(Outcome::Done(result.return_value.clone().unwrap_or(Json::Null))),
+            Some(_) => Ok(Outcome::Pending),
+            None => {
+                tasks.push(task(single, None));
+                Ok(Outcome::Pending)
+            }
+        },
+        StepKind::Condition => {
+            let condition = single.remove(&0).unwrap_or(Json::Null);
+            let Json::Bool(branch) = condition else {
+                return Err(format!("Condition step '{}' expected a boolean, got {}", step.name, condition));
+            };
+            Ok(Outcome::Routed {
+                branch,
+                value: single.remove(&1).unwrap_or(Json::Bool(branch)),
+            })
+        }
+        StepKind::Map => {
+            let items = single.remove(&0).unwrap_or(Json::Null);
+            let Json::Array(items) = items else {
+                return Err(format!("Map step '{}' expected an array, got {}", step.name, items));
+            };
+
+            let mut done: Vec<(i32, Json)> = results
+                .iter()
+                .filter(|result| result.status == ResultStatus::Success)
+                .map(|result| (result.map_index.unwrap_or_default(), result.return_value.clone().unwrap_or(Json::Null)))
+                .collect();
+            if done.len() == items.len() {
+                done.sort_by_key(|(index, _)| *index);
+                return Ok(Outcome::Mapped(done.into_iter().map(|(_, value)| value).collect()));
+            }
+
+            // Elements are enqueued in order, so the next one is the number of those enqueued so far.
+            let running = results.len() - done.len();
+            let free = (step.concurrency.unwrap_or(1) as usize).saturating_sub(running);
+            for (index, item) in items.into_iter().enumerate().skip(results.len()).take(free) {
+                let mut inputs = single.clone();
+                inputs.insert(0, item);
+                tasks.push(task(inputs, Some(index as i32)));
+            }
+            Ok(Outcome::Pending)
+        }
+        StepKind::Join => Ok(Outcome::Done(Json::Array(inputs.into_iter().flat_map(|(_, values)| values).collect()))),
+    }
+}
+
+/// Ends a run that cannot complete, its pending steps are dropped and running ones finish on their own.
+async fn fail<C: ConnectionTrait>(db: &C, run: &WorkflowRunModel, status: ResultStatus, message: String) -> Result<(), DbErr> {
+    CodeResult::update_many()
+        .col_expr(
+            CodeResultColumn::Status,
+            CodeResultColumn::Status.save_as(Expr::val(ResultStatus::Cancelled)),
+        )
+        .col_expr(CodeResultColumn::FinishedAt, Expr::value(TimeDateTimeWithTimeZone::now_utc()))
+        .filter(CodeResultColumn::WorkflowRunId.eq(run.id))
+        .filter(CodeResultColumn::Status.eq(ResultStatus::Pending))
+        .exec(db)
+        .await?;
+
+    finish(db, run, status, Some(message)).await
+}
+
+async fn finish<C: ConnectionTrait>(db: &C, run: &WorkflowRunModel, status: ResultStatus, message: Option<String>) -> Result<(), DbErr> {
+    tracing::info!("Workflow run {} finished with status {}", run.run_id, status);
+
+    let mut finished: WorkflowRunActiveModel = run.clone().into();
+    finished.status = Set(status);
+    finished.error_message = Set(message);
+    finished.finished_at = Set(Some(TimeDateTimeWithTimeZone::now_utc()));
+    finished.update(db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str, kind: StepKind) -> Step {
+        let runs_code = matches!(kind, StepKind::Code | StepKind::Map);
+        Step {
+            name: name.to_string(),
+            kind,
+            code_node_id: runs_code.then_some(1),
+            version: None,
+            args: vec![],
+            concurrency: None,
+        }
+    }
+
+    fn edge(from_step: &str, to_step: &str, argument: i32, branch: Option<bool>) -> Edge {
+        Edge {
+            id: 0,
+            workflow_id: 0,
+            from_step: from_step.to_string(),
+            to_step: to_step.to_string(),
+            argument,
+            branch,
+        }
+    }
+
+    fn names(steps: &[Step]) -> Vec<&str> {
+        steps.iter().map(|step| step.name.as_str()).collect()
+    }
+
+    #[test]
+    fn validate_sorts_steps_after_their_inputs() {
+        let steps = [
+            step("summarize", StepKind::Code),
+            step("fetch", StepKind::Code),
+            step("log", StepKind::Code),
+        ];
+        let edges = [edge("fetch", "summarize", 0, None)];
+
+        assert_eq!(names(&validate(&steps, &edges).unwrap()), ["fetch", "summarize", "log"]);
+    }
+
+    #[test]
+    fn validate_rejects_cycles() {
+        let steps = [step("a", StepKind::Code), step("b", StepKind::Code), step("c", StepKind::Code)];
+        let edges = [edge("a", "b", 0, None), edge("b", "c", 0, None), edge("c", "b", 1, None)];
+
+        let err = validate(&steps, &edges).unwrap_err();
+        assert!(err.contains("cycle") && err.contains("b, c"), "{}", err);
+    }
+
+    #[test]
+    fn validate_rejects_dangling_edges() {
+        let steps = [step("a", StepKind::Code)];
+
+        assert!(
+            validate(&steps, &[edge("a", "missing", 0, None)])
+                .unwrap_err()
+                .contains("unknown step 'missing'")
+        );
+        assert!(
+            validate(&steps, &[edge("missing", "a", 0, None)])
+                .unwrap_err()
+                .contains("unknown step 'missing'")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_arguments_out_of_range_or_taken_twice() {
+        let steps = [step("a", StepKind::Code), step("b", StepKind::Code), step("c", StepKind::Code)];
+
+        assert!(validate(&steps, &[edge("a", "c", 1, None)]).unwrap_err().contains("out of range"));
+        assert!(
+            validate(&steps, &[edge("a", "c", 0, None), edge("b", "c", 0, None)])
+                .unwrap_err()
+                .contains("several incoming edges")
+        );
+    }
+
+    #[test]
+    fn validate_accepts_condition_branches() {
+        let steps = [
+            step("check", StepKind::Code),
+            step("if", StepKind::Condition),
+            step("then", StepKind::Code),
+            step("else", StepKind::Code),
+        ];
+        let edges = [
+            edge("check", "if", 0, None),
+            edge("if", "then", 0, Some(true)),
+            edge("if", "else", 0, Some(false)),
+        ];
+
+        assert_eq!(names(&validate(&steps, &edges).unwrap()), ["check", "if", "then", "else"]);
+    }
+
+    #[test]
+    fn validate_rejects_misplaced_branches() {
+        let steps = [
+            step("check", StepKind::Code),
+            step("if", StepKind::Condition),
+            step("then", StepKind::Code),
+        ];
+
+        let unbranched = [edge("check", "if", 0, None), edge("if", "then", 0, None)];
+        assert!(validate(&steps, &unbranched).unwrap_err().contains("need a branch"));
+
+        let branched = [edge("check", "if", 0, Some(true)), edge("if", "then", 0, Some(true))];
+        assert!(validate(&steps, &branched).unwrap_err().contains("Only edges from condition steps"));
+    }
+
+    #[test]
+    fn validate_rejects_conditions_without_a_boolean() {
+        let steps = [step("check", StepKind::Code), step("if", StepKind::Condition)];
+
+        assert!(validate(&steps, &[]).unwrap_err().contains("takes a boolean as argument 0"));
+        assert!(
+            validate(&steps, &[edge("check", "if", 1, None)])
+                .unwrap_err()
+                .contains("takes a boolean as argument 0")
+        );
+    }
+
+    #[test]
+    fn validate_requires_a_join_after_a_map() {
+        let steps = [step("list", StepKind::Code), step("each", StepKind::Map), step("next", StepKind::Code)];
+
+        let edges = [edge("list", "each", 0, None), edge("each", "next", 0, None)];
+        assert!(validate(&steps, &edges).unwrap_err().contains("must lead to a join"));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::enums::ResultStatus;
+use crate::workflow_edges::Model as Edge;
+use crate::workflows::Step;
+use sea_orm::FromJsonQueryResult;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The steps and edges a run follows, copied from the workflow when it starts so later edits do not affect it.
+///
+/// Steps are in topological order and pinned to a version.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, FromJsonQueryResult)]
+pub struct Graph {
+    pub steps: Vec<Step>,
+    pub edges: Vec<Edge>,
+}
+
+/// A run of a workflow, each step records a `code_result` under it, see `workflow_engine`.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "workflow_runs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub run_id: uuid::Uuid,
+    pub workflow_id: i32,
+    /// `Running` until every step succeeded, or one of them did not.
+    pub status: ResultStatus,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub graph: Graph,
+    pub error_message: Option<String>,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+    pub finished_at: Option<TimeDateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        fk_name = "fk-workflow_runs-workflow_id",
+        belongs_to = "super::workflows::Entity",
+        from = "Column::WorkflowId",
+        to = "super::workflows::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Workflow,
+    #[sea_orm(has_many = "super::code_result::Entity")]
+    CodeResults,
+}
+
+impl Related<super::workflows::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Workflow.def()
+    }
+}
+
+impl Related<super::code_result::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CodeResults.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
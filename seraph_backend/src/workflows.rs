@@ -0,0 +1,84 @@
+use sea_orm::FromJsonQueryResult;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a step does with its inputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    /// Runs a code node.
+    #[default]
+    Code,
+    /// Takes a boolean as argument 0 and only follows the outgoing edges of the matching `branch`.
+    /// They carry argument 1 when it has one, the boolean otherwise.
+    Condition,
+    /// Takes an array as argument 0 and runs the code node once per element, passed in its place.
+    Map,
+    /// Collects the values of its incoming edges into an array, in argument order. An edge from a map step brings
+    /// every element result, in element order. Runs once the steps before it finished or were skipped.
+    Join,
+}
+
+/// A step of a workflow.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Step {
+    /// Identifies the step within its workflow, the same node can run in several steps.
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: StepKind,
+    /// Node run by code and map steps.
+    #[serde(default)]
+    pub code_node_id: Option<i32>,
+    /// Version of the node to run, the current one when the run starts if unset.
+    #[serde(default)]
+    pub version: Option<i32>,
+    /// JSON encoded arguments, in the positions left free by the incoming edges.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Element runs of a map step allowed at the same time, 1 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+}
+
+/// Steps of a workflow, persisted as a JSONB array.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, FromJsonQueryResult)]
+#[serde(transparent)]
+pub struct Steps(pub Vec<Step>);
+
+/// A graph of code nodes, the return value of a step is passed as an argument to the steps its edges lead to.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "workflows")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub name: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub steps: Steps,
+
+    #[sea_orm(default_expr = "Expr::current_timestamp()")]
+    pub created_at: TimeDateTimeWithTimeZone,
+    pub updated_at: TimeDateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::workflow_edges::Entity")]
+    Edges,
+    #[sea_orm(has_many = "super::workflow_runs::Entity")]
+    Runs,
+}
+
+impl Related<super::workflow_edges::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Edges.def()
+    }
+}
+
+impl Related<super::workflow_runs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Runs.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
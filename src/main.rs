@@ -1,5 +1,3 @@
-use seraph_backend;
-
 fn main() {
     seraph_backend::server::main()
 }